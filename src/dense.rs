@@ -0,0 +1,415 @@
+//! A secondary map that stores its values contiguously for fast iteration.
+//! ```
+//! use slotmap::{DenseSecondaryMap, SlotMap};
+//!
+//! let mut positions = SlotMap::new();
+//! let mut velocities = DenseSecondaryMap::new();
+//!
+//! let a = positions.insert(0.0);
+//! let b = positions.insert(1.0);
+//! velocities.insert(b, 0.5);
+//!
+//! for velocity in velocities.values() {
+//!     println!("{velocity}");
+//! }
+//!
+//! assert_eq!(velocities.get(a), None);
+//! assert_eq!(velocities.get(b), Some(&0.5));
+//! ```
+
+use crate::{invalid_key, Key};
+use std::fmt::{self, Debug};
+use std::iter::FusedIterator;
+use std::ops::{Index, IndexMut};
+
+pub struct Iter<'a, V>(std::slice::Iter<'a, (Key, V)>);
+pub struct Values<'a, V>(Iter<'a, V>);
+
+/// A map from the keys of a slotmap to values of another type, which uses
+/// indirection to allow packing values next to each other.
+///
+/// # Performance
+/// #### Access
+/// Insertion, removal and access are constant time operations and involve
+/// a single level of indirection to map the slot index to the item index.
+/// #### Iteration
+/// All key value pairs are stored contigously in a vector, so iteration is as
+/// fast as possible regardless of how many keys have associated values.
+#[derive(Clone, Default)]
+pub struct DenseSecondaryMap<V> {
+    items: Vec<(Key, V)>,
+    slots: Vec<Option<usize>>,
+}
+
+impl<V> DenseSecondaryMap<V> {
+    #[must_use]
+    pub fn new() -> DenseSecondaryMap<V> {
+        DenseSecondaryMap {
+            items: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Associates a value with a key, returning the value previously
+    /// associated with the same key.
+    /// See [`SecondaryMap::insert`](crate::SecondaryMap::insert)
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert_eq!(secondary.insert(key, 1), None);
+    /// assert_eq!(secondary.insert(key, 2), Some(1));
+    /// ```
+    pub fn insert(&mut self, key: Key, value: V) -> Option<V> {
+        if key.is_null() {
+            return None;
+        }
+        let index = key.index();
+        if index >= self.slots.len() {
+            self.slots.resize(index + 1, None);
+        }
+        if let Some(indirect_index) = self.slots[index] {
+            let item = &mut self.items[indirect_index];
+            if item.0.generation == key.generation && item.0.map_id.accepts(key.map_id) {
+                return Some(std::mem::replace(&mut item.1, value));
+            } else if item.0.generation <= key.generation {
+                *item = (key, value);
+            }
+        } else {
+            self.items.push((key, value));
+            self.slots[index] = Some(self.items.len() - 1);
+        }
+        None
+    }
+
+    /// Removes the value associated with a key.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// assert_eq!(secondary.remove(key), Some(1));
+    /// assert_eq!(secondary.remove(key), None);
+    /// ```
+    pub fn remove(&mut self, key: Key) -> Option<V> {
+        let indirect_index = self.indirect_index(key)?;
+        self.slots[key.index()] = None;
+        let (_, value) = self.items.swap_remove(indirect_index);
+        if let Some((moved, _)) = self.items.get(indirect_index) {
+            self.slots[moved.index()] = Some(indirect_index);
+        }
+        Some(value)
+    }
+
+    /// Returns a shared reference to the value associated with the key.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// assert_eq!(secondary.get(key), Some(&1));
+    /// ```
+    #[must_use]
+    pub fn get(&self, key: Key) -> Option<&V> {
+        self.indirect_index(key)
+            .map(|indirect_index| &self.items[indirect_index].1)
+    }
+
+    /// Returns an exclusive reference to the value associated with the key and
+    /// otherwise behaves indentically to `get`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// *secondary.get_mut(key).unwrap() += 1;
+    /// assert_eq!(secondary.get(key), Some(&2));
+    /// ```
+    #[must_use]
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut V> {
+        self.indirect_index(key)
+            .map(|indirect_index| &mut self.items[indirect_index].1)
+    }
+
+    /// Checks whether a value is associated with the key.
+    #[must_use]
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of keys that have an associated value.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if no keys have an associated value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an iterator that yields a (key, value) tuple for every key that
+    /// has an associated value.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let key = slotmap.insert(i);
+    ///     secondary.insert(key, i * 2);
+    /// }
+    ///
+    /// for (key, val) in &secondary {
+    ///     println!("{:?}: {}", key, val);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter(self.items.iter())
+    }
+
+    /// Iterate over values in the map.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{DenseSecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = DenseSecondaryMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let key = slotmap.insert(i);
+    ///     secondary.insert(key, i * 2);
+    /// }
+    ///
+    /// for value in secondary.values() {
+    ///     println!("{value}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn values(&self) -> Values<'_, V> {
+        Values(self.iter())
+    }
+
+    fn indirect_index(&self, key: Key) -> Option<usize> {
+        match self.slots.get(key.index()).copied() {
+            Some(Some(indirect_index))
+                if self.items[indirect_index].0.generation == key.generation
+                    && self.items[indirect_index].0.map_id.accepts(key.map_id) =>
+            {
+                Some(indirect_index)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<V> Index<Key> for DenseSecondaryMap<V> {
+    type Output = V;
+    fn index(&self, index: Key) -> &Self::Output {
+        match self.get(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+impl<V> IndexMut<Key> for DenseSecondaryMap<V> {
+    fn index_mut(&mut self, index: Key) -> &mut Self::Output {
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+impl<V: Debug> Debug for DenseSecondaryMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Key, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(key, value)| (*key, value)).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, V> Iterator for Values<'a, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<V> DoubleEndedIterator for Iter<'_, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .map(|(key, value)| (*key, value))
+            .next_back()
+    }
+}
+
+impl<V> DoubleEndedIterator for Values<'_, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next_back()
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<V> ExactSizeIterator for Values<'_, V> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<V> FusedIterator for Iter<'_, V> {}
+impl<V> FusedIterator for Values<'_, V> {}
+
+impl<'a, V> IntoIterator for &'a DenseSecondaryMap<V> {
+    type Item = (Key, &'a V);
+    type IntoIter = Iter<'a, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SecondaryMap, SlotMap};
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = DenseSecondaryMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        assert_eq!(secondary.insert(a, 1), None);
+        assert_eq!(secondary.insert(c, 3), None);
+        assert_eq!(secondary.insert(c, 4), Some(3));
+        assert_eq!(secondary.len(), 2);
+        assert_eq!(secondary.get(a), Some(&1));
+        assert_eq!(secondary.get(b), None);
+        assert_eq!(secondary.get(c), Some(&4));
+        *secondary.get_mut(a).unwrap() += 1;
+        assert_eq!(secondary[a], 2);
+        assert_eq!(secondary.remove(a), Some(2));
+        assert_eq!(secondary.remove(a), None);
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary[c], 4);
+    }
+
+    #[test]
+    fn test_stale_keys() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = DenseSecondaryMap::new();
+        let a = slotmap.insert("a");
+        secondary.insert(a, 1);
+        slotmap.remove(a);
+        let b = slotmap.insert("b");
+        assert_eq!(secondary.get(b), None);
+        assert_eq!(secondary.insert(b, 2), None);
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary.get(a), None);
+        assert_eq!(secondary.insert(a, 3), None);
+        assert_eq!(secondary.remove(a), None);
+        assert_eq!(secondary.get(b), Some(&2));
+        assert_eq!(secondary.insert(Key::null(), 4), None);
+        assert_eq!(secondary.get(Key::null()), None);
+    }
+
+    #[test]
+    fn test_iter_matches_sparse() {
+        let mut slotmap = SlotMap::new();
+        let mut sparse = SecondaryMap::new();
+        let mut dense = DenseSecondaryMap::new();
+        let keys = (0..20).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        for (x, &key) in keys.iter().enumerate() {
+            sparse.insert(key, x);
+            dense.insert(key, x);
+        }
+        for &key in keys.iter().step_by(3) {
+            sparse.remove(key);
+            dense.remove(key);
+        }
+        let mut a = dense
+            .iter()
+            .map(|(key, &value)| (key, value))
+            .collect::<Vec<_>>();
+        a.sort_unstable();
+        let b = sparse
+            .iter()
+            .map(|(key, &value)| (key, value))
+            .collect::<Vec<_>>();
+        assert_eq!(a, b);
+        assert_eq!(dense.values().len(), sparse.len());
+    }
+
+    #[test]
+    fn test_default() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = DenseSecondaryMap::default();
+        assert!(secondary.is_empty());
+        let key = slotmap.insert("a");
+        assert_eq!(secondary.insert(key, 1), None);
+        assert_eq!(secondary.get(key), Some(&1));
+    }
+
+    #[test]
+    #[cfg(feature = "checked_keys")]
+    fn test_checked_keys() {
+        let mut a = SlotMap::new();
+        let mut b = SlotMap::new();
+        let mut secondary = DenseSecondaryMap::new();
+        let x = a.insert("x");
+        let y = b.insert("y");
+        secondary.insert(x, 1);
+        assert_eq!(secondary.get(y), None);
+        assert_eq!(secondary.get(Key::from_ffi(x.as_ffi())), Some(&1));
+        assert_eq!(secondary.insert(y, 2), None);
+        assert_eq!(secondary.get(y), Some(&2));
+        assert_eq!(secondary.get(x), None);
+        assert_eq!(secondary.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key with index 0 and generation 0")]
+    fn test_index_panic() {
+        let mut slotmap = SlotMap::new();
+        let secondary = DenseSecondaryMap::<i32>::new();
+        let key = slotmap.insert("a");
+        let _ = secondary[key];
+    }
+}
@@ -33,7 +33,48 @@
 
 #![deny(clippy::pedantic)]
 
-use std::ops::{Index, IndexMut};
+#[cfg(feature = "rayon")]
+use rayon::iter::plumbing::{Consumer, ProducerCallback, UnindexedConsumer};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::iter::{FusedIterator, Rev};
+use std::ops::{Index, IndexMut, Range};
+#[cfg(feature = "checked_keys")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub mod dense;
+pub mod secondary;
+pub mod typed;
+
+pub use dense::DenseSecondaryMap;
+pub use secondary::{get_both_mut, SecondaryMap};
+pub use std::collections::TryReserveError;
+pub use typed::{TypedKey, TypedSlotMap};
+
+/// The item yielded by [`Iter`](crate::Iter).
+/// ##### Example
+/// ```
+/// use slotmap::{IterItem, SlotMap};
+///
+/// fn first<T>(slotmap: &SlotMap<T>) -> Option<IterItem<'_, T>> {
+///     slotmap.iter().next()
+/// }
+///
+/// let mut slotmap = SlotMap::new();
+/// let key = slotmap.insert("an example value");
+/// assert_eq!(first(&slotmap), Some((key, &"an example value")));
+/// ```
+pub type IterItem<'a, T> = (Key, &'a T);
+/// The item yielded by [`IterMut`](crate::IterMut).
+pub type IterMutItem<'a, T> = (Key, &'a mut T);
+/// The item yielded by [`IntoIter`](crate::IntoIter) and
+/// [`Drain`](crate::Drain).
+pub type IntoIterItem<T> = (Key, T);
 
 pub struct Iter<'a, T: 'a>(std::slice::Iter<'a, Item<T>>);
 pub struct IterMut<'a, T: 'a>(std::slice::IterMut<'a, Item<T>>);
@@ -42,31 +83,155 @@ pub struct Values<'a, T>(Iter<'a, T>);
 pub struct ValuesMut<'a, T>(IterMut<'a, T>);
 pub struct IntoValues<T>(IntoIter<T>);
 pub struct Keys<'a, T>(Iter<'a, T>);
+pub struct IntoKeys<T>(IntoIter<T>);
+pub struct Drain<'a, T>(&'a mut SlotMap<T>);
+pub struct CursorMut<'a, T> {
+    slotmap: &'a mut SlotMap<T>,
+    position: usize,
+}
+pub struct DrainFilter<'a, T, F>
+where
+    F: FnMut(Key, &mut T) -> bool,
+{
+    slotmap: &'a mut SlotMap<T>,
+    position: usize,
+    f: F,
+}
+#[cfg(feature = "rayon")]
+pub struct ParIter<'a, T: Sync>(rayon::slice::Iter<'a, Item<T>>);
+#[cfg(feature = "rayon")]
+pub struct ParIterMut<'a, T: Send>(rayon::slice::IterMut<'a, Item<T>>);
 
 /// A unique handle to a value in a slotmap.
 /// ##### Memory use
 /// The key is the size of a `u64` + `usize`, which is 16 bytes on 64 bit platforms.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// With the `key32` feature enabled both the index and the generation are
+/// `u32`, which shrinks the key to 8 bytes. The `checked_keys` feature adds
+/// a `u64` map id on top of that.
+/// ##### Checked keys
+/// With the `checked_keys` feature enabled every slotmap is assigned an id
+/// when it's created, and keys issued by one slotmap never resolve in
+/// another. Clones share the id of the slotmap they were cloned from.
+/// ##### Ordering
+/// Keys are ordered by their slot index first and then by their generation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Key {
-    index: usize,
+    index: RawIndex,
     generation: Generation,
+    #[cfg_attr(all(feature = "serde", not(feature = "checked_keys")), serde(skip))]
+    #[cfg_attr(all(feature = "serde", feature = "checked_keys"), serde(default))]
+    map_id: MapId,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) struct Generation(pub u64);
+#[cfg(not(feature = "key32"))]
+type RawIndex = usize;
+#[cfg(not(feature = "key32"))]
+type RawGeneration = u64;
+#[cfg(feature = "key32")]
+type RawIndex = u32;
+#[cfg(feature = "key32")]
+type RawGeneration = u32;
+
+/// The generation of a slot, which is advanced every time the slot's value is
+/// removed so that stale keys stop resolving.
+///
+/// Generations are a `u64`, or a `u32` with the `key32` feature enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Generation(RawGeneration);
+
+/// Identifies the slotmap that issued a key. This is zero sized unless the
+/// `checked_keys` feature is enabled. An id of `0` is unchecked and is
+/// accepted by every slotmap, which is what keys without an id such as
+/// those from [`Key::from_ffi`](crate::Key::from_ffi) use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    all(feature = "serde", feature = "checked_keys"),
+    derive(Serialize, Deserialize),
+    serde(transparent)
+)]
+pub(crate) struct MapId {
+    #[cfg(feature = "checked_keys")]
+    id: u64,
+}
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Item<T> {
     value: T,
     key: Key,
+    #[cfg(feature = "insertion_order")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    seq: u64,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 enum Slot {
     Occupied(usize),
     Vacant(Generation),
 }
 
+/// The reason a key failed to resolve, returned by
+/// [`SlotMap::get_checked`](crate::SlotMap::get_checked).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyError {
+    /// The key's index is beyond every slot in the slotmap.
+    OutOfRange,
+    /// The key's slot exists but holds a different generation, so the value
+    /// the key referred to has been removed.
+    StaleGeneration,
+    /// The key was issued by a different slotmap. This is only detected with
+    /// the `checked_keys` feature enabled.
+    WrongMap,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::OutOfRange => f.write_str("key index is out of range"),
+            KeyError::StaleGeneration => f.write_str("key generation is stale"),
+            KeyError::WrongMap => f.write_str("key belongs to another slotmap"),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// The reason a value couldn't be inserted, returned by
+/// [`SlotMap::try_insert`](crate::SlotMap::try_insert). Both variants hand
+/// the value back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertError<T> {
+    /// The slotmap already holds its maximum number of values.
+    Full(T),
+    /// There are no reusable slots and every index a key can represent is
+    /// already in use, which is only a practical concern with the `key32`
+    /// feature.
+    Exhausted(T),
+}
+
+impl<T> InsertError<T> {
+    /// Returns the value that couldn't be inserted.
+    pub fn into_inner(self) -> T {
+        match self {
+            InsertError::Full(value) | InsertError::Exhausted(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Display for InsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::Full(_) => f.write_str("slotmap is full"),
+            InsertError::Exhausted(_) => f.write_str("slotmap has no usable slots left"),
+        }
+    }
+}
+
+impl<T: Debug> std::error::Error for InsertError<T> {}
+
 /// A slotmap that uses indirection for accesses to allow packing values next to each other.
 ///
 /// # Performance
@@ -76,706 +241,4471 @@ enum Slot {
 ///
 /// Removing values doesn't require shifting any elements. This is done using
 /// [`Vec::swap_remove`](Vec::swap_remove) interally and then updating the indirect indexes as needed.
-/// Shrinking the underlying storage is not supported.
+/// Shrinking the underlying storage is only possible for trailing vacant
+/// slots, see [`SlotMap::shrink_to_fit`](crate::SlotMap::shrink_to_fit).
 /// #### Iteration
 /// All key value pairs are stored contigously in a vector, so iteration is as
 /// fast as possible.
-#[derive(Clone, Default)]
+/// #### Serialization
+/// With the `serde` feature enabled the complete internal state is serialized,
+/// so keys from before serialization resolve to the same values after
+/// deserialization.
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct SlotMap<T> {
     items: Vec<Item<T>>,
     slots: Vec<Slot>,
     free: Vec<usize>,
+    // The generation given to newly created slots. This is raised when slots
+    // are discarded so that stale keys for their indices can't resolve again.
+    fresh_generation: Generation,
+    #[cfg_attr(all(feature = "serde", not(feature = "checked_keys")), serde(skip))]
+    id: MapId,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_capacity: Option<usize>,
+    #[cfg(feature = "insertion_order")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    next_seq: u64,
 }
 
-impl Generation {
-    pub fn next(self) -> Generation {
-        Generation(self.0 + 1)
-    }
-}
-
-impl Slot {
-    pub fn unwrap_occupied(self) -> usize {
-        match self {
-            Slot::Occupied(i) => i,
-            Slot::Vacant(_) => panic!(),
+impl Key {
+    /// Returns a key that never resolves to a value in any slotmap. This can be
+    /// used in place of an `Option<Key>` to save memory.
+    ///
+    /// A null key is never returned by `insert`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{Key, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let _ = slotmap.insert("an example value");
+    /// assert!(slotmap.get(Key::null()).is_none());
+    /// ```
+    #[must_use]
+    pub fn null() -> Key {
+        Key {
+            index: RawIndex::MAX,
+            generation: Generation(0),
+            map_id: MapId::default(),
         }
     }
-}
 
-impl<T> SlotMap<T> {
+    /// Returns true if this is the null key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{Key, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(Key::null().is_null());
+    /// assert!(!key.is_null());
+    /// ```
     #[must_use]
-    pub fn new() -> SlotMap<T> {
-        SlotMap {
-            items: Vec::new(),
-            slots: Vec::new(),
-            free: Vec::new(),
-        }
+    pub fn is_null(&self) -> bool {
+        self.index == RawIndex::MAX
     }
 
-    /// Inserts a value into the slotmap. This returns a unique key that can
-    /// later be be used to access and remove values.
-    /// ##### Slot reuse
-    /// Insert will reuse vacant slots when they are available similar to an
-    /// arena.
+    /// Returns the index of the slot this key refers to.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
     /// let key = slotmap.insert("an example value");
+    /// assert_eq!(key.index(), 0);
     /// ```
     #[must_use]
-    #[allow(clippy::match_on_vec_items)]
-    pub fn insert(&mut self, value: T) -> Key {
-        if let Some(index) = self.free.pop() {
-            match self.slots[index] {
-                Slot::Vacant(generation) => {
-                    let key = Key { index, generation };
-                    self.items.push(Item { value, key });
-                    self.slots[index] = Slot::Occupied(self.items.len() - 1);
-                    key
-                }
-                Slot::Occupied(_) => unreachable!(),
-            }
-        } else {
-            let key = Key {
-                index: self.slots.len(),
-                generation: Generation(0),
-            };
-            self.items.push(Item { value, key });
-            self.slots.push(Slot::Occupied(self.items.len() - 1));
-            key
-        }
+    #[allow(clippy::unnecessary_cast)]
+    pub fn index(&self) -> usize {
+        self.index as usize
     }
 
-    /// Removes the value associated with a key from the slotmap.
-    /// This will return `None` if provided with a stale key.
+    /// Returns the generation of the slot this key refers to.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
     /// let key = slotmap.insert("an example value");
-    /// assert!(matches!(
-    ///     slotmap.remove(key),
-    ///     Some("an example value")
-    /// ));
-    /// assert!(slotmap.remove(key).is_none());
+    /// assert_eq!(key.generation(), 0);
     /// ```
-    #[allow(clippy::match_on_vec_items)]
-    #[allow(clippy::missing_panics_doc)]
-    pub fn remove(&mut self, key: Key) -> Option<T> {
-        if self.get(key).is_some() {
-            let indirect_index = self.slots[key.index].unwrap_occupied();
-            self.free.push(indirect_index);
-            self.slots[key.index] = Slot::Vacant(key.generation.next());
-            if indirect_index == self.items.len() - 1 {
-                self.items.pop().map(|i| i.value)
-            } else {
-                let last_item_index = self.items.last().unwrap().key.index;
-                self.slots[last_item_index] = Slot::Occupied(indirect_index);
-                Some(self.items.swap_remove(indirect_index).value)
-            }
-        } else {
-            None
-        }
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation.value()
     }
 
-    /// Returns a shared reference to the value associated with the key.
-    /// Attempting to retrive a value that has been removed will return `None`.
-    /// This method should be used instead of indexing if you aren't sure that
-    /// a value still exists.
+    /// Returns true if this key refers to the given slot index and generation.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
     /// let key = slotmap.insert("an example value");
-    /// assert!(matches!(
-    ///     slotmap.get(key).copied(),
-    ///     Some("an example value")
-    /// ));
-    /// slotmap.remove(key);
-    /// assert!(slotmap.get(key).is_none());
+    /// assert!(key.matches(0, 0));
+    /// assert!(!key.matches(0, 1));
     /// ```
     #[must_use]
-    pub fn get(&self, key: Key) -> Option<&T> {
-        match self.slots.get(key.index).copied() {
-            Some(Slot::Occupied(indirect_index))
-                if self.items[indirect_index].key.generation == key.generation =>
-            {
-                Some(&self.items[indirect_index].value)
-            }
-            _ => None,
-        }
+    pub fn matches(&self, index: usize, generation: u64) -> bool {
+        self.index() == index && self.generation() == generation
     }
 
-    /// Returns an exclusive reference to the value associated with the key and
-    /// otherwise behaves indentically to `get`.
+    /// Packs the key into a single `u64`, suitable for passing over FFI or
+    /// storing in binary formats.
+    /// ##### Truncation
+    /// The low 32 bits hold the index and the high 32 bits hold the
+    /// generation. Only the low 32 bits of each are kept, so keys with an
+    /// index or generation above `u32::MAX` will not round trip through
+    /// [`Key::from_ffi`](crate::Key::from_ffi). The map id used by the
+    /// `checked_keys` feature isn't packed either, so unpacked keys are
+    /// accepted by every slotmap.
+    ///
+    /// The null key is packed with an index of `u32::MAX`, which is unpacked
+    /// as the null key again.
     /// ##### Example
     /// ```
-    /// use slotmap::SlotMap;
+    /// use slotmap::{Key, SlotMap};
     ///
     /// let mut slotmap = SlotMap::new();
-    /// let key = slotmap.insert("an example value".to_string());
-    /// *slotmap.get_mut(key).unwrap() += " that has been mutated";
-    /// assert!(matches!(
-    ///     slotmap.get(key).map(|s| s.as_str()),
-    ///     Some("an example value that has been mutated")
-    /// ));
+    /// let key = slotmap.insert("an example value");
+    /// let unpacked = Key::from_ffi(key.as_ffi());
+    /// assert_eq!(slotmap[unpacked], "an example value");
     /// ```
     #[must_use]
-    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
-        match self.slots.get(key.index).copied() {
-            Some(Slot::Occupied(indirect_index))
-                if self.items[indirect_index].key.generation == key.generation =>
-            {
-                Some(&mut self.items[indirect_index].value)
-            }
-            _ => None,
+    pub fn as_ffi(&self) -> u64 {
+        (self.index() as u64 & 0xFFFF_FFFF) | (self.generation() << 32)
+    }
+
+    fn new(index: usize, generation: Generation, map_id: MapId) -> Option<Key> {
+        RawIndex::try_from(index)
+            .ok()
+            .filter(|&index| index != RawIndex::MAX)
+            .map(|index| Key {
+                index,
+                generation,
+                map_id,
+            })
+    }
+
+    /// Unpacks a key previously packed with [`Key::as_ffi`](crate::Key::as_ffi).
+    /// An index of `u32::MAX` is unpacked as the null key's index, so the null
+    /// key round trips.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_ffi(value: u64) -> Key {
+        let index = value & 0xFFFF_FFFF;
+        Key {
+            index: if index == 0xFFFF_FFFF {
+                RawIndex::MAX
+            } else {
+                index as RawIndex
+            },
+            generation: Generation((value >> 32) as RawGeneration),
+            map_id: MapId::default(),
         }
     }
+}
 
-    /// Returns the number of occupied slots.
+/// Formats the key as `Key(index@generation)`, or `Key(null)` for the null
+/// key.
+/// ##### Example
+/// ```
+/// use slotmap::{Key, SlotMap};
+///
+/// let mut slotmap = SlotMap::new();
+/// let key = slotmap.insert("an example value");
+/// assert_eq!(format!("{key:?}"), "Key(0@0)");
+/// assert_eq!(format!("{:?}", Key::null()), "Key(null)");
+/// ```
+impl Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_null() {
+            write!(f, "Key(null)")
+        } else {
+            write!(f, "Key({}@{})", self.index(), self.generation())
+        }
+    }
+}
+
+/// Returns the null key, see [`Key::null`](crate::Key::null).
+impl Default for Key {
+    fn default() -> Key {
+        Key::null()
+    }
+}
+
+impl Generation {
+    /// Creates a generation from its raw value.
+    /// ##### Panics
+    /// Panics if the value doesn't fit in a `u32` with the `key32` feature
+    /// enabled.
     /// ##### Example
     /// ```
-    /// use slotmap::SlotMap;
-    ///
-    /// let mut slotmap = SlotMap::new();
+    /// use slotmap::Generation;
     ///
-    /// for _ in 0..10 {
-    ///     let _ = slotmap.insert(());
-    /// }
+    /// let generation = Generation::new(5);
+    /// assert_eq!(generation.value(), 5);
+    /// ```
+    #[must_use]
+    #[allow(clippy::useless_conversion)]
+    pub fn new(value: u64) -> Generation {
+        Generation(RawGeneration::try_from(value).expect("generation out of range"))
+    }
+
+    /// Returns the raw value of the generation.
+    #[must_use]
+    #[allow(clippy::useless_conversion)]
+    pub fn value(&self) -> u64 {
+        u64::from(self.0)
+    }
+
+    /// Returns the generation that follows this one, or `None` if the
+    /// generation can't be advanced any further.
+    /// ##### Example
+    /// ```
+    /// use slotmap::Generation;
     ///
-    /// assert_eq!(slotmap.len(), 10);
+    /// assert_eq!(Generation::new(0).next(), Some(Generation::new(1)));
     /// ```
     #[must_use]
-    pub fn len(&self) -> usize {
-        self.items.len()
+    pub fn next(self) -> Option<Generation> {
+        self.0.checked_add(1).map(Generation)
+    }
+}
+
+impl MapId {
+    #[cfg(feature = "checked_keys")]
+    fn unique() -> MapId {
+        // A `u64` is wide enough that the counter never wraps back around to
+        // the unchecked id in practice.
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        MapId {
+            id: NEXT.fetch_add(1, Ordering::Relaxed),
+        }
     }
 
-    /// Returns true if there are no occupied slots.
+    #[cfg(not(feature = "checked_keys"))]
+    fn unique() -> MapId {
+        MapId {}
+    }
+
+    #[cfg(feature = "checked_keys")]
+    pub(crate) fn accepts(self, other: MapId) -> bool {
+        self.id == 0 || other.id == 0 || self.id == other.id
+    }
+
+    #[cfg(not(feature = "checked_keys"))]
+    #[allow(clippy::unused_self)]
+    pub(crate) fn accepts(self, _: MapId) -> bool {
+        true
+    }
+}
+
+impl<T> SlotMap<T> {
+    /// Creates an empty slotmap without allocating. This is the same as
+    /// [`SlotMap::default`](crate::SlotMap::default).
+    #[must_use]
+    pub fn new() -> SlotMap<T> {
+        SlotMap {
+            items: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            fresh_generation: Generation(0),
+            id: MapId::unique(),
+            max_capacity: None,
+            #[cfg(feature = "insertion_order")]
+            next_seq: 0,
+        }
+    }
+
+    /// Creates a slotmap that holds at most `max_capacity` values when
+    /// inserting with [`SlotMap::try_insert`](crate::SlotMap::try_insert).
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
-    /// let mut slotmap = SlotMap::new();
-    /// let _ = slotmap.insert(());
-    /// assert!(!slotmap.is_empty());
+    /// let mut slotmap = SlotMap::with_max_capacity(1);
+    /// assert!(slotmap.try_insert("an example value").is_ok());
+    /// assert!(slotmap.try_insert("another example value").is_err());
     /// ```
     #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.items.len() == 0
+    pub fn with_max_capacity(max_capacity: usize) -> SlotMap<T> {
+        SlotMap {
+            max_capacity: Some(max_capacity),
+            ..SlotMap::new()
+        }
     }
 
-    /// Checks whether a key is still valid.
+    /// Takes the slotmap apart into its key value pairs in storage order,
+    /// along with the key each reusable vacant slot will hand out next. These
+    /// can be stored in a custom format and given to
+    /// [`SlotMap::from_raw_parts`](crate::SlotMap::from_raw_parts) to rebuild
+    /// an equivalent slotmap.
     ///
-    /// This is functionally equivalent to calling `is_some`
-    /// on the `Option` returned by `get`.
+    /// With the `insertion_order` feature enabled the pairs are listed in
+    /// insertion order instead, which is the order
+    /// [`SlotMap::from_raw_parts`](crate::SlotMap::from_raw_parts) takes as
+    /// their insertion order.
+    ///
+    /// The vacant keys are listed in the reverse of the order the slots will
+    /// be reused in. They may include a key for an index past every slot,
+    /// which keeps the generations of slots discarded by
+    /// [`SlotMap::shrink_to_fit`](crate::SlotMap::shrink_to_fit) from being
+    /// handed out again.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
-    /// let key = slotmap.insert("an example value");
-    /// assert!(slotmap.contains_key(key));
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// slotmap.remove(a);
+    ///
+    /// let (pairs, vacant) = slotmap.into_raw_parts();
+    /// assert_eq!(pairs, [(b, "b")]);
+    /// assert_eq!(vacant.len(), 1);
     /// ```
     #[must_use]
-    pub fn contains_key(&self, key: Key) -> bool {
-        self.get(key).is_some()
+    pub fn into_raw_parts(self) -> (Vec<(Key, T)>, Vec<Key>) {
+        let fresh = (self.fresh_generation > Generation(0))
+            .then(|| Key::new(self.slots.len(), self.fresh_generation, self.id))
+            .flatten();
+        let vacant = fresh
+            .into_iter()
+            .chain(
+                self.free
+                    .iter()
+                    .filter_map(|&index| match self.slots[index] {
+                        Slot::Vacant(generation) => Key::new(index, generation, self.id),
+                        Slot::Occupied(_) => None,
+                    }),
+            )
+            .collect();
+        #[allow(unused_mut)]
+        let mut items = self.items;
+        #[cfg(feature = "insertion_order")]
+        items.sort_unstable_by_key(|item| item.seq);
+        let pairs = items
+            .into_iter()
+            .map(|item| (item.key, item.value))
+            .collect();
+        (pairs, vacant)
     }
 
-    /// Remove all items that do not satisfy a predicate.
-    /// ##### Performance
-    /// Removing elements does not require shifting elements but
-    /// does change the ordering of items.
+    /// Rebuilds a slotmap from the parts returned by
+    /// [`SlotMap::into_raw_parts`](crate::SlotMap::into_raw_parts) without
+    /// changing any of the keys. Values are stored in the order they are
+    /// given, which is also taken as their insertion order with the
+    /// `insertion_order` feature. The slots of the vacant keys are reused in
+    /// reverse order,
+    /// each starting at its key's generation. Slots created later start at
+    /// the highest generation of any vacant key.
+    ///
+    /// Slots below the highest index that have neither a value nor a vacant
+    /// key are retired, so stale keys for them can never resolve again.
+    /// Memory for the slots is proportional to the highest index, not the
+    /// number of values.
+    ///
+    /// This returns `None` if any key is null, if two keys share a slot
+    /// index, or if the keys were issued by different slotmaps.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// let (pairs, vacant) = slotmap.into_raw_parts();
     ///
-    /// for i in 0..10 {
-    ///    let _ = slotmap.insert(i);
-    /// }
-    ///
-    /// slotmap.retain(|(key, val)| val % 2 == 0);
-    ///
-    /// assert_eq!(slotmap.len(), 5);
+    /// let slotmap = SlotMap::from_raw_parts(pairs, vacant).unwrap();
+    /// assert_eq!(slotmap[key], "an example value");
     /// ```
-    pub fn retain<F>(&mut self, f: F)
-    where
-        F: Fn((Key, &T)) -> bool,
-    {
-        let mut i = 0;
-        while i < self.items.len() {
-            let key = self.items[i].key;
-            let val = &self.items[i].value;
-            if f((key, val)) {
-                i += 1;
-            } else {
-                self.remove(key);
+    #[must_use]
+    pub fn from_raw_parts(pairs: Vec<(Key, T)>, vacant: Vec<Key>) -> Option<SlotMap<T>> {
+        let keys = || pairs.iter().map(|(key, _)| key).chain(&vacant);
+        let id = keys()
+            .map(|key| key.map_id)
+            .find(|&id| id != MapId::default())
+            .unwrap_or_else(MapId::unique);
+        if keys().any(|key| key.is_null() || !id.accepts(key.map_id)) {
+            return None;
+        }
+        let len = keys().map(|key| key.index() + 1).max().unwrap_or(0);
+        let fresh_generation = vacant
+            .iter()
+            .map(|key| key.generation)
+            .max()
+            .unwrap_or_default();
+        let mut slotmap = SlotMap {
+            items: Vec::with_capacity(pairs.len()),
+            slots: vec![Slot::Vacant(Generation(RawGeneration::MAX)); len],
+            free: Vec::with_capacity(vacant.len()),
+            fresh_generation,
+            id,
+            max_capacity: None,
+            #[cfg(feature = "insertion_order")]
+            next_seq: 0,
+        };
+        let mut seen = vec![false; len];
+        for key in vacant {
+            if std::mem::replace(&mut seen[key.index()], true) {
+                return None;
             }
+            slotmap.slots[key.index()] = Slot::Vacant(key.generation);
+            slotmap.free.push(key.index());
         }
+        for (key, value) in pairs {
+            if std::mem::replace(&mut seen[key.index()], true) {
+                return None;
+            }
+            slotmap.slots[key.index()] = Slot::Occupied(slotmap.push_item(key, value));
+        }
+        Some(slotmap)
     }
 
-    /// Returns an iterator that yields a (key, value) tuple for every
-    /// occupied slot in the slotmap.
+    /// Sets the maximum number of values
+    /// [`SlotMap::try_insert`](crate::SlotMap::try_insert) will allow the
+    /// slotmap to hold. Values that are already stored are never removed.
+    pub fn set_max_capacity(&mut self, max_capacity: usize) {
+        self.max_capacity = Some(max_capacity);
+    }
+
+    /// Inserts a value into the slotmap. This returns a unique key that can
+    /// later be be used to access and remove values.
+    /// ##### Slot reuse
+    /// Insert will reuse vacant slots when they are available similar to an
+    /// arena.
+    /// ##### Panics
+    /// Panics if every index a key can represent is already in use, which is
+    /// only a practical concern with the `key32` feature.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
-    ///
-    /// for i in 0..10 {
-    ///    let _ = slotmap.insert(i * 2);
-    /// }
-    ///
-    /// for (key, val) in &slotmap {
-    ///    println!("{:?}: {}", key, val);
-    /// }
+    /// let key = slotmap.insert("an example value");
     /// ```
     #[must_use]
-    pub fn iter(&self) -> Iter<T> {
-        Iter(self.items.iter())
+    pub fn insert(&mut self, value: T) -> Key {
+        self.insert_with_key(|_| value)
     }
 
-    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    /// Inserts a value into the slotmap, reusing the vacant slot with the
+    /// lowest index instead of the most recently vacated one. This makes index
+    /// assignment independent of the order values were removed in.
+    /// ##### Performance
+    /// Finding the lowest index scans every vacant slot, so this is linear in
+    /// the number of vacant slots where
+    /// [`SlotMap::insert`](crate::SlotMap::insert) is constant time.
+    /// ##### Panics
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
-    ///
-    /// for i in 0..10 {
-    ///     let _ = slotmap.insert(i);
-    /// }
-    ///
-    /// for (_key, val) in &mut slotmap {
-    ///     *val *= 2;
-    /// }
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// slotmap.remove(a);
+    /// slotmap.remove(b);
+    /// let c = slotmap.insert_low("c");
+    /// assert_eq!(c.index(), a.index());
     /// ```
     #[must_use]
-    pub fn iter_mut(&mut self) -> IterMut<T> {
-        IterMut(self.items.iter_mut())
+    pub fn insert_low(&mut self, value: T) -> Key {
+        if let Some((position, _)) = self.free.iter().enumerate().min_by_key(|(_, &index)| index) {
+            let last = self.free.len() - 1;
+            self.free.swap(position, last);
+        }
+        self.insert(value)
     }
 
-    /// Iterate over values in the slotmap.
+    /// Inserts a value into the slotmap, returning its key along with an
+    /// exclusive reference to the stored value.
+    /// ##### Panics
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
+    /// let (key, value) = slotmap.insert_mut(String::from("an example value"));
+    /// value.push_str(" that has been mutated");
+    /// assert_eq!(slotmap[key], "an example value that has been mutated");
+    /// ```
+    pub fn insert_mut(&mut self, value: T) -> (Key, &mut T) {
+        let key = self.insert(value);
+        let item = self
+            .items
+            .last_mut()
+            .expect("inserted value is stored last");
+        (key, &mut item.value)
+    }
+
+    /// Inserts a value produced by a closure that is given the key the value
+    /// will be stored under. This is useful for values that need to know their
+    /// own key.
+    /// ##### Panics
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
+    /// ##### Example
+    /// ```
+    /// use slotmap::{Key, SlotMap};
     ///
-    /// for i in 0..10 {
-    ///     let _ = slotmap.insert(i);
+    /// struct Node {
+    ///     this: Key,
     /// }
     ///
-    /// for value in slotmap.values() {
-    ///     println!("{value}");
-    /// }
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert_with_key(|this| Node { this });
+    /// assert_eq!(slotmap[key].this, key);
+    /// ```
     #[must_use]
-    pub fn values(&self) -> Values<T> {
-        Values(self.iter())
+    pub fn insert_with_key<F>(&mut self, f: F) -> Key
+    where
+        F: FnOnce(Key) -> T,
+    {
+        // The slot is only taken off the free list once the value has been
+        // built, so it isn't lost if the closure panics.
+        if let Some(&index) = self.free.last() {
+            match self.slots[index] {
+                Slot::Vacant(generation) => {
+                    let key = Key::new(index, generation, self.id).unwrap();
+                    let value = f(key);
+                    self.free.pop();
+                    self.slots[index] = Slot::Occupied(self.push_item(key, value));
+                    key
+                }
+                Slot::Occupied(_) => unreachable!(),
+            }
+        } else {
+            let key = Key::new(self.slots.len(), self.fresh_generation, self.id)
+                .expect("slotmap index space exhausted");
+            let indirect_index = self.push_item(key, f(key));
+            self.slots.push(Slot::Occupied(indirect_index));
+            key
+        }
     }
 
-    /// See [`SlotMap::values`](crate::SlotMap::values)
-    ///
-    /// Iterate over mutable references to values in the slotmap.
+    /// Inserts every value from an iterator, returning the assigned keys in
+    /// insertion order.
+    /// ##### Panics
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
+    /// let keys = slotmap.insert_many(["a", "b", "c"]);
+    /// assert_eq!(slotmap[keys[1]], "b");
+    /// ```
+    pub fn insert_many<I: IntoIterator<Item = T>>(&mut self, iter: I) -> Vec<Key> {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.reserve(lower);
+        let mut keys = Vec::with_capacity(lower);
+        keys.extend(iter.map(|value| self.insert(value)));
+        keys
+    }
+
+    /// Inserts a value into the slotmap unless it already holds its maximum
+    /// number of values or has no usable slots left.
     ///
-    /// for i in 0..10 {
-    ///     let _ = slotmap.insert(i);
-    /// }
+    /// The maximum is set with
+    /// [`SlotMap::with_max_capacity`](crate::SlotMap::with_max_capacity) or
+    /// [`SlotMap::set_max_capacity`](crate::SlotMap::set_max_capacity), and
+    /// is only enforced by this method.
+    /// ##### Errors
+    /// Returns [`InsertError::Full`](crate::InsertError::Full) if the slotmap
+    /// is full, and [`InsertError::Exhausted`](crate::InsertError::Exhausted)
+    /// in the case where [`SlotMap::insert`](crate::SlotMap::insert) would
+    /// panic. Both hand the value back.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{InsertError, SlotMap};
     ///
-    /// for mut value in slotmap.values_mut() {
-    ///     *value += 1;
-    /// }
-    #[must_use]
-    pub fn values_mut(&mut self) -> ValuesMut<T> {
-        ValuesMut(self.iter_mut())
+    /// let mut slotmap = SlotMap::with_max_capacity(1);
+    /// let key = slotmap.try_insert("an example value").unwrap();
+    /// assert_eq!(
+    ///     slotmap.try_insert("another example value"),
+    ///     Err(InsertError::Full("another example value"))
+    /// );
+    /// ```
+    pub fn try_insert(&mut self, value: T) -> Result<Key, InsertError<T>> {
+        match self.insert_error(self.slots.len()) {
+            Some(error) => Err(error(value)),
+            None => Ok(self.insert(value)),
+        }
     }
 
-    /// See [`SlotMap::values`](crate::SlotMap::values)
+    /// Removes the value associated with a key from the slotmap.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Generation overflow
+    /// A slot whose generation can't be advanced any further is retired
+    /// instead of being made available for reuse, so stale keys can never
+    /// resolve to a newer value.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
     ///
-    /// Consume slotmap and iterate over the keys.
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(matches!(
+    ///     slotmap.remove(key),
+    ///     Some("an example value")
+    /// ));
+    /// assert!(slotmap.remove(key).is_none());
+    /// ```
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let value = self.remove_no_recycle(key)?;
+        if key.generation.next().is_some() {
+            self.free.push(key.index());
+        }
+        Some(value)
+    }
+
+    /// Removes the value associated with a key like
+    /// [`SlotMap::remove`](crate::SlotMap::remove), also returning the key the
+    /// slot will be given the next time it is reused. The returned key has the
+    /// same index and the next generation.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Generation overflow
+    /// If the slot is retired because its generation can't be advanced any
+    /// further, the null key is returned since the slot will never be reused.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// let (_, next) = slotmap.remove_and_next_key(key).unwrap();
+    /// assert_eq!(slotmap.insert("another example value"), next);
+    /// ```
+    pub fn remove_and_next_key(&mut self, key: Key) -> Option<(T, Key)> {
+        let value = self.remove(key)?;
+        let next = key
+            .generation
+            .next()
+            .and_then(|generation| Key::new(key.index(), generation, self.id))
+            .unwrap_or_else(Key::null);
+        Some((value, next))
+    }
+
+    /// Removes the value associated with a key like
+    /// [`SlotMap::remove`](crate::SlotMap::remove), also returning the key of
+    /// the value that was moved into the removed value's position in storage,
+    /// if any. This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
     ///
-    /// for i in 0..10 {
-    ///     let _ = slotmap.insert(i);
-    /// }
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(slotmap.remove_reporting(a), Some(("a", Some(b))));
+    /// assert_eq!(slotmap.remove_reporting(b), Some(("b", None)));
+    /// ```
+    pub fn remove_reporting(&mut self, key: Key) -> Option<(T, Option<Key>)> {
+        let indirect_index = self.indirect_index(key)?;
+        let value = self.remove(key)?;
+        let moved = self.items.get(indirect_index).map(|item| item.key);
+        Some((value, moved))
+    }
+
+    /// Removes the value associated with a key from the slotmap without
+    /// making its slot available for reuse. This will return `None` if
+    /// provided with a stale key.
+    /// ##### Slot retirement
+    /// The vacated slot is never handed out again by `insert`, so its index
+    /// will never refer to a different value. Each retired slot permanently
+    /// occupies a small amount of memory.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
     ///
-    /// let values = slotmap.into_values().collect::<Vec<i32>>();
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("an example value");
+    /// assert!(matches!(
+    ///     slotmap.remove_no_recycle(a),
+    ///     Some("an example value")
+    /// ));
+    /// let b = slotmap.insert("another example value");
+    /// assert_ne!(a, b);
     /// ```
-    #[must_use]
-    pub fn into_values(self) -> IntoValues<T> {
-        IntoValues(self.into_iter())
+    pub fn remove_no_recycle(&mut self, key: Key) -> Option<T> {
+        let indirect_index = self.indirect_index(key)?;
+        let last_index = self.items.last()?.key.index();
+        // Every lookup that can fail is done before anything is changed, so
+        // the slotmap is never left half updated.
+        if self.slots.get(last_index) != Some(&Slot::Occupied(self.items.len() - 1)) {
+            debug_assert!(false, "the last item's slot must refer back to it");
+            return None;
+        }
+        self.slots[key.index()] = Slot::Vacant(key.generation.next().unwrap_or(key.generation));
+        let item = self.items.swap_remove(indirect_index);
+        if indirect_index < self.items.len() {
+            self.slots[last_index] = Slot::Occupied(indirect_index);
+        }
+        Some(item.value)
     }
 
-    /// Iterate over keys in the slotmap.
+    /// Invalidates a key while keeping its value in place, returning a new key
+    /// for the value. The old key becomes stale.
+    /// This will return `None` if provided with a stale key, or if the key's
+    /// generation can't be advanced any further, in which case the old key
+    /// stays valid.
     /// ##### Example
     /// ```
     /// use slotmap::SlotMap;
     ///
     /// let mut slotmap = SlotMap::new();
+    /// let old = slotmap.insert("an example value");
+    /// let new = slotmap.reissue(old).unwrap();
+    /// assert!(slotmap.get(old).is_none());
+    /// assert_eq!(slotmap[new], "an example value");
+    /// ```
+    pub fn reissue(&mut self, key: Key) -> Option<Key> {
+        let indirect_index = self.indirect_index(key)?;
+        let new = Key {
+            generation: key.generation.next()?,
+            ..key
+        };
+        self.items[indirect_index].key = new;
+        Some(new)
+    }
+
+    /// Removes the values associated with each of the keys, returning how many
+    /// values were actually removed. Stale and repeated keys are ignored.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
     ///
-    /// for i in 0..10 {
-    ///     let _ = slotmap.insert(i);
-    /// }
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(slotmap.remove_many(&[a, b, a]), 2);
+    /// assert!(slotmap.is_empty());
+    /// ```
+    pub fn remove_many(&mut self, keys: &[Key]) -> usize {
+        keys.iter()
+            .filter(|&&key| self.remove(key).is_some())
+            .count()
+    }
+
+    /// Returns a shared reference to the value associated with the key.
+    /// Attempting to retrive a value that has been removed will return `None`.
+    /// This method should be used instead of indexing if you aren't sure that
+    /// a value still exists.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
     ///
-    /// for key in slotmap.keys() {
-    ///     println!("{:?}", key);
-    /// }
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(matches!(
+    ///     slotmap.get(key).copied(),
+    ///     Some("an example value")
+    /// ));
+    /// slotmap.remove(key);
+    /// assert!(slotmap.get(key).is_none());
     /// ```
     #[must_use]
-    pub fn keys(&self) -> Keys<T> {
-        Keys(self.iter())
+    pub fn get(&self, key: Key) -> Option<&T> {
+        self.indirect_index(key)
+            .map(|indirect_index| &self.items[indirect_index].value)
     }
-}
 
-impl<T> Index<Key> for SlotMap<T> {
-    type Output = T;
-    fn index(&self, index: Key) -> &Self::Output {
-        self.get(index).unwrap()
+    /// Returns the stored key along with a shared reference to its value.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(matches!(
+    ///     slotmap.get_key_value(key),
+    ///     Some((k, &"an example value")) if k == key
+    /// ));
+    /// ```
+    #[must_use]
+    pub fn get_key_value(&self, key: Key) -> Option<(Key, &T)> {
+        self.indirect_index(key).map(|indirect_index| {
+            let item = &self.items[indirect_index];
+            (item.key, &item.value)
+        })
     }
-}
 
-impl<T> IndexMut<Key> for SlotMap<T> {
-    fn index_mut(&mut self, index: Key) -> &mut Self::Output {
-        self.get_mut(index).unwrap()
+    /// Returns the first key and value in the slotmap's storage order.
+    /// ##### Ordering
+    /// Storage order matches insertion order until a value is removed, at
+    /// which point the last value is moved into the removed value's place.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(slotmap.first_key_value(), Some((a, &"a")));
+    /// slotmap.remove(a);
+    /// assert_eq!(slotmap.first_key_value(), Some((b, &"b")));
+    /// ```
+    #[must_use]
+    pub fn first_key_value(&self) -> Option<(Key, &T)> {
+        self.items.first().map(|item| (item.key, &item.value))
     }
-}
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = (Key, &'a T);
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|item| (item.key, &item.value)).next()
+    /// Returns the last key and value in the slotmap's storage order.
+    /// See [`SlotMap::first_key_value`](crate::SlotMap::first_key_value)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(slotmap.last_key_value(), Some((b, &"b")));
+    /// ```
+    #[must_use]
+    pub fn last_key_value(&self) -> Option<(Key, &T)> {
+        self.items.last().map(|item| (item.key, &item.value))
     }
-}
 
-impl<'a, T> Iterator for IterMut<'a, T> {
-    type Item = (Key, &'a mut T);
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0
-            .by_ref()
-            .map(|item| (item.key, &mut item.value))
-            .next()
+    /// Returns an exclusive reference to the value associated with the key and
+    /// otherwise behaves indentically to `get`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value".to_string());
+    /// *slotmap.get_mut(key).unwrap() += " that has been mutated";
+    /// assert!(matches!(
+    ///     slotmap.get(key).map(|s| s.as_str()),
+    ///     Some("an example value that has been mutated")
+    /// ));
+    /// ```
+    #[must_use]
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        self.indirect_index(key)
+            .map(|indirect_index| &mut self.items[indirect_index].value)
     }
-}
 
-impl<T> Iterator for IntoIter<T> {
-    type Item = (Key, T);
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|item| (item.key, item.value)).next()
+    /// Returns a shared reference to the value associated with the key, or
+    /// the reason the key doesn't resolve.
+    /// ##### Errors
+    /// Returns [`KeyError::OutOfRange`](crate::KeyError::OutOfRange) if the
+    /// key's index has no slot and
+    /// [`KeyError::StaleGeneration`](crate::KeyError::StaleGeneration) if the
+    /// key's value has been removed.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{KeyError, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert_eq!(slotmap.get_checked(key), Ok(&"an example value"));
+    /// slotmap.remove(key);
+    /// assert_eq!(slotmap.get_checked(key), Err(KeyError::StaleGeneration));
+    /// ```
+    pub fn get_checked(&self, key: Key) -> Result<&T, KeyError> {
+        self.checked_indirect_index(key)
+            .map(|indirect_index| &self.items[indirect_index].value)
     }
-}
 
-impl<'a, T> Iterator for Values<'a, T> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next()
+    /// See [`SlotMap::get_checked`](crate::SlotMap::get_checked)
+    /// ##### Errors
+    /// See [`SlotMap::get_checked`](crate::SlotMap::get_checked)
+    pub fn get_checked_mut(&mut self, key: Key) -> Result<&mut T, KeyError> {
+        self.checked_indirect_index(key)
+            .map(|indirect_index| &mut self.items[indirect_index].value)
     }
-}
 
-impl<'a, T> Iterator for ValuesMut<'a, T> {
-    type Item = &'a mut T;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next()
+    /// Returns a shared reference to the value associated with the key without
+    /// checking that the key is still valid.
+    /// ##### Safety
+    /// The key must be live, meaning it was returned by this slotmap and its
+    /// value hasn't been removed since. Using any other key is undefined
+    /// behavior.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert_eq!(unsafe { *slotmap.get_unchecked(key) }, "an example value");
+    /// ```
+    #[must_use]
+    pub unsafe fn get_unchecked(&self, key: Key) -> &T {
+        // SAFETY: a live key always refers to an occupied slot that points
+        // into `items`.
+        unsafe {
+            match *self.slots.get_unchecked(key.index()) {
+                Slot::Occupied(indirect_index) => &self.items.get_unchecked(indirect_index).value,
+                Slot::Vacant(_) => std::hint::unreachable_unchecked(),
+            }
+        }
     }
-}
 
-impl<T> Iterator for IntoValues<T> {
-    type Item = T;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next()
+    /// Returns an exclusive reference to the value associated with the key
+    /// without checking that the key is still valid.
+    /// ##### Safety
+    /// See [`SlotMap::get_unchecked`](crate::SlotMap::get_unchecked)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert(1);
+    /// unsafe { *slotmap.get_unchecked_mut(key) += 1 };
+    /// assert_eq!(slotmap[key], 2);
+    /// ```
+    #[must_use]
+    pub unsafe fn get_unchecked_mut(&mut self, key: Key) -> &mut T {
+        // SAFETY: a live key always refers to an occupied slot that points
+        // into `items`.
+        unsafe {
+            match *self.slots.get_unchecked(key.index()) {
+                Slot::Occupied(indirect_index) => {
+                    &mut self.items.get_unchecked_mut(indirect_index).value
+                }
+                Slot::Vacant(_) => std::hint::unreachable_unchecked(),
+            }
+        }
     }
-}
 
-impl<'a, T> Iterator for Keys<'a, T> {
-    type Item = Key;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(key, _)| key).next()
+    /// Returns exclusive references to the values associated with several
+    /// keys at once.
+    /// This will return `None` if any of the keys are stale or if any two keys
+    /// refer to the same value.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert(1);
+    /// let b = slotmap.insert(2);
+    /// let [x, y] = slotmap.get_disjoint_mut([a, b]).unwrap();
+    /// std::mem::swap(x, y);
+    /// assert_eq!(slotmap[a], 2);
+    /// assert_eq!(slotmap[b], 1);
+    /// assert!(slotmap.get_disjoint_mut([a, a]).is_none());
+    /// ```
+    #[must_use]
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [Key; N]) -> Option<[&mut T; N]> {
+        let mut indirect_indexes = [0; N];
+        for (i, key) in keys.into_iter().enumerate() {
+            let indirect_index = self.indirect_index(key)?;
+            if indirect_indexes[..i].contains(&indirect_index) {
+                return None;
+            }
+            indirect_indexes[i] = indirect_index;
+        }
+        let items = self.items.as_mut_ptr();
+        // SAFETY: every index was validated against `items` and they are all
+        // distinct, so none of the returned references alias.
+        Some(indirect_indexes.map(|i| unsafe { &mut (*items.add(i)).value }))
     }
-}
 
-impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0
-            .by_ref()
-            .map(|item| (item.key, &item.value))
-            .next_back()
+    /// Returns shared references to the values associated with a slice of
+    /// keys, in the same order as the keys. Stale keys give `None`, and keys
+    /// may be repeated.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// slotmap.remove(a);
+    /// assert_eq!(slotmap.get_all(&[a, b, b]), [None, Some(&"b"), Some(&"b")]);
+    /// ```
+    #[must_use]
+    pub fn get_all(&self, keys: &[Key]) -> Vec<Option<&T>> {
+        keys.iter().map(|&key| self.get(key)).collect()
+    }
+
+    /// Returns exclusive references to the values associated with a slice of
+    /// keys, in the same order as the keys.
+    /// This will return `None` if any of the keys are stale or if any two keys
+    /// refer to the same value.
+    /// See [`SlotMap::get_disjoint_mut`](crate::SlotMap::get_disjoint_mut)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let keys = (0..10).map(|i| slotmap.insert(i)).collect::<Vec<_>>();
+    ///
+    /// for value in slotmap.get_each_mut(&keys[..5]).unwrap() {
+    ///     *value *= 2;
+    /// }
+    ///
+    /// assert_eq!(slotmap[keys[4]], 8);
+    /// ```
+    #[must_use]
+    pub fn get_each_mut(&mut self, keys: &[Key]) -> Option<Vec<&mut T>> {
+        let mut seen = HashSet::with_capacity(keys.len());
+        let mut indirect_indexes = Vec::with_capacity(keys.len());
+        for &key in keys {
+            let indirect_index = self.indirect_index(key)?;
+            if !seen.insert(indirect_index) {
+                return None;
+            }
+            indirect_indexes.push(indirect_index);
+        }
+        let items = self.items.as_mut_ptr();
+        // SAFETY: every index was validated against `items` and they are all
+        // distinct, so none of the returned references alias.
+        Some(
+            indirect_indexes
+                .into_iter()
+                .map(|i| unsafe { &mut (*items.add(i)).value })
+                .collect(),
+        )
+    }
+
+    /// Returns exclusive references to the values associated with two keys at
+    /// once. See [`SlotMap::get_disjoint_mut`](crate::SlotMap::get_disjoint_mut)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert(1);
+    /// let b = slotmap.insert(2);
+    /// let (x, y) = slotmap.get2_mut(a, b).unwrap();
+    /// *x += *y;
+    /// assert_eq!(slotmap[a], 3);
+    /// ```
+    #[must_use]
+    pub fn get2_mut(&mut self, a: Key, b: Key) -> Option<(&mut T, &mut T)> {
+        self.get_disjoint_mut([a, b]).map(|[a, b]| (a, b))
+    }
+
+    /// Replaces the value associated with the key, returning the old value.
+    /// This will return `None` and drop the new value if provided with a stale
+    /// key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(matches!(
+    ///     slotmap.replace(key, "a replacement value"),
+    ///     Some("an example value")
+    /// ));
+    /// assert_eq!(slotmap[key], "a replacement value");
+    /// ```
+    pub fn replace(&mut self, key: Key, value: T) -> Option<T> {
+        self.get_mut(key).map(|old| std::mem::replace(old, value))
+    }
+
+    /// Swaps the values associated with two keys. The keys continue to be
+    /// valid and resolve to each other's previous values.
+    /// This will return `false` and leave the slotmap unchanged if either key
+    /// is stale.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert!(slotmap.swap(a, b));
+    /// assert_eq!(slotmap[a], "b");
+    /// assert_eq!(slotmap[b], "a");
+    /// ```
+    pub fn swap(&mut self, a: Key, b: Key) -> bool {
+        if a == b {
+            return self.contains_key(a);
+        }
+        match self.get2_mut(a, b) {
+            Some((a, b)) => {
+                std::mem::swap(a, b);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns an exclusive reference to the value associated with the key,
+    /// inserting the value returned by the closure if the key isn't live.
+    /// ##### Slot reuse
+    /// A value is only inserted when the key's slot is vacant and waiting to
+    /// be reused with exactly the key's generation, in which case the value is
+    /// stored in that same slot and the key becomes live. Keys whose value was
+    /// removed are stale rather than reusable, because removal advances the
+    /// slot's generation.
+    ///
+    /// Nothing is inserted and `None` is returned if the key is stale, refers
+    /// to a retired slot or belongs to another slotmap, since an inserted
+    /// value couldn't be reached with the key.
+    /// ##### Panics
+    /// Panics if the key's index is out of range.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert(1);
+    /// *slotmap.get_or_insert_with(key, || 0).unwrap() += 1;
+    /// assert_eq!(slotmap[key], 2);
+    ///
+    /// slotmap.remove(key);
+    /// assert!(slotmap.get_or_insert_with(key, || 0).is_none());
+    /// ```
+    #[track_caller]
+    pub fn get_or_insert_with<F>(&mut self, key: Key, f: F) -> Option<&mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        assert!(
+            key.index() < self.slots.len(),
+            "index {} is out of range for a slotmap with {} slots",
+            key.index(),
+            self.slots.len()
+        );
+        if !self.id.accepts(key.map_id) {
+            return None;
+        }
+        match self.slots[key.index()] {
+            Slot::Occupied(indirect_index)
+                if self.items[indirect_index].key.generation == key.generation =>
+            {
+                Some(&mut self.items[indirect_index].value)
+            }
+            Slot::Vacant(generation) if generation == key.generation => {
+                let free_index = self.free.iter().position(|&i| i == key.index())?;
+                self.free.swap_remove(free_index);
+                let indirect_index = self.push_item(key, f());
+                self.slots[key.index()] = Slot::Occupied(indirect_index);
+                Some(&mut self.items[indirect_index].value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns an exclusive reference to the value associated with the key,
+    /// inserting `T::default()` into the key's slot if it is vacant and
+    /// reusable. Returns `None` for stale keys. See
+    /// [`SlotMap::get_or_insert_with`](crate::SlotMap::get_or_insert_with).
+    /// ##### Panics
+    /// Panics if the key's index is out of range.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert(1);
+    /// *slotmap.get_mut_or_default(key).unwrap() += 1;
+    /// assert_eq!(slotmap[key], 2);
+    /// ```
+    #[track_caller]
+    pub fn get_mut_or_default(&mut self, key: Key) -> Option<&mut T>
+    where
+        T: Default,
+    {
+        self.get_or_insert_with(key, T::default)
+    }
+
+    /// Returns the number of occupied slots.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for _ in 0..10 {
+    ///     let _ = slotmap.insert(());
+    /// }
+    ///
+    /// assert_eq!(slotmap.len(), 10);
+    /// ```
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if there are no occupied slots.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let _ = slotmap.insert(());
+    /// assert!(!slotmap.is_empty());
+    /// ```
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.len() == 0
+    }
+
+    /// Returns the number of values the slotmap can hold without reallocating.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::<()>::new();
+    /// slotmap.reserve(10);
+    /// assert!(slotmap.capacity() >= 10);
+    /// ```
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        let slots = self.slots.capacity() - self.slots.len() + self.free.len() + self.len();
+        self.items.capacity().min(slots)
+    }
+
+    /// Returns the number of bytes allocated by the slotmap's internal
+    /// vectors. This counts allocated capacity rather than live values, and
+    /// doesn't include any heap memory owned by the values themselves.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::<u64>::new();
+    /// assert_eq!(slotmap.memory_usage(), 0);
+    /// let _ = slotmap.insert(1);
+    /// assert!(slotmap.memory_usage() > 0);
+    /// ```
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.items.capacity() * std::mem::size_of::<Item<T>>()
+            + self.slots.capacity() * std::mem::size_of::<Slot>()
+            + self.free.capacity() * std::mem::size_of::<usize>()
+    }
+
+    /// Returns every slot's index along with the position of its value in the
+    /// slotmap's storage, or `None` if the slot is vacant. This is meant for
+    /// debugging and allocates a new vector on every call.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let _ = slotmap.insert("b");
+    /// slotmap.remove(a);
+    /// assert_eq!(slotmap.debug_slot_map(), [(0, None), (1, Some(0))]);
+    /// ```
+    #[must_use]
+    pub fn debug_slot_map(&self) -> Vec<(usize, Option<usize>)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| match slot {
+                Slot::Occupied(indirect_index) => (index, Some(*indirect_index)),
+                Slot::Vacant(_) => (index, None),
+            })
+            .collect()
+    }
+
+    /// Reserves capacity for at least `additional` more values.
+    /// ##### Panics
+    /// Panics if the new capacity overflows `usize`, or aborts if allocation
+    /// fails. See [`SlotMap::try_reserve`](crate::SlotMap::try_reserve) for a
+    /// fallible version.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::<()>::new();
+    /// slotmap.reserve(10);
+    /// assert!(slotmap.capacity() >= 10);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.items.reserve(additional);
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Tries to reserve capacity for at least `additional` more values.
+    /// ##### Errors
+    /// Returns an error if the new capacity overflows `usize` or if allocation
+    /// fails, in which case the slotmap is still usable.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::<()>::new();
+    /// assert!(slotmap.try_reserve(10).is_ok());
+    /// assert!(slotmap.try_reserve(usize::MAX).is_err());
+    /// ```
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.items.try_reserve(additional)?;
+        self.slots
+            .try_reserve(additional.saturating_sub(self.free.len()))
+    }
+
+    /// Checks whether a key is still valid.
+    ///
+    /// This is functionally equivalent to calling `is_some`
+    /// on the `Option` returned by `get`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert!(slotmap.contains_key(key));
+    /// ```
+    #[must_use]
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of values that satisfy a predicate.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let slotmap = (0..10).collect::<SlotMap<_>>();
+    /// assert_eq!(slotmap.count_matching(|value| value % 2 == 0), 5);
+    /// ```
+    pub fn count_matching<F: Fn(&T) -> bool>(&self, f: F) -> usize {
+        self.items.iter().filter(|item| f(&item.value)).count()
+    }
+
+    /// Returns true if every key in the slice is associated with a value.
+    /// This is true for an empty slice.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert!(slotmap.contains_all(&[a, b]));
+    /// slotmap.remove(b);
+    /// assert!(!slotmap.contains_all(&[a, b]));
+    /// ```
+    #[must_use]
+    pub fn contains_all(&self, keys: &[Key]) -> bool {
+        keys.iter().all(|&key| self.contains_key(key))
+    }
+
+    /// Shrinks the storage of the slotmap as much as possible. Reusable vacant
+    /// slots at the end of the slotmap are discarded, while every slot before
+    /// the last occupied or retired slot is kept so that existing keys still
+    /// resolve.
+    /// ##### Stale keys
+    /// Slots created for the discarded indices later on start after the
+    /// highest generation that was discarded, so stale keys for those indices
+    /// stay stale.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let keys = (0..10).map(|i| slotmap.insert(i)).collect::<Vec<_>>();
+    ///
+    /// for key in &keys[1..] {
+    ///     slotmap.remove(*key);
+    /// }
+    ///
+    /// slotmap.shrink_to_fit();
+    /// assert_eq!(slotmap[keys[0]], 0);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        let mut reusable = vec![false; self.slots.len()];
+        for &index in &self.free {
+            reusable[index] = true;
+        }
+        let len = reusable
+            .iter()
+            .rposition(|reusable| !reusable)
+            .map_or(0, |index| index + 1);
+        for slot in self.slots.drain(len..) {
+            if let Slot::Vacant(generation) = slot {
+                self.fresh_generation = self.fresh_generation.max(generation);
+            }
+        }
+        self.free.retain(|&index| index < len);
+        self.items.shrink_to_fit();
+        self.slots.shrink_to_fit();
+        self.shrink_free_list();
+    }
+
+    /// Shrinks the storage used to track reusable vacant slots. Without
+    /// calling this, that storage stays as large as the highest number of
+    /// vacant slots the slotmap has ever had at once.
+    /// [`SlotMap::shrink_to_fit`](crate::SlotMap::shrink_to_fit) also does
+    /// this.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let keys = slotmap.insert_many(0..100);
+    ///
+    /// for key in keys {
+    ///     slotmap.remove(key);
+    /// }
+    ///
+    /// slotmap.insert_many(0..100);
+    /// slotmap.shrink_free_list();
+    /// ```
+    pub fn shrink_free_list(&mut self) {
+        self.free.shrink_to_fit();
+    }
+
+    /// Stops reusing the slotmap's current vacant slots, so later inserts
+    /// are given fresh slots with increasing indices until values are removed
+    /// again. Live values are unaffected.
+    /// ##### Memory use
+    /// Forgotten slots are retired rather than discarded. They stay allocated
+    /// for the lifetime of the slotmap and are not trimmed by
+    /// [`SlotMap::shrink_to_fit`](crate::SlotMap::shrink_to_fit), which keeps
+    /// stale keys for those slots from ever resolving again.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// slotmap.remove(a);
+    /// slotmap.forget_free_slots();
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(b.index(), 1);
+    /// ```
+    pub fn forget_free_slots(&mut self) {
+        self.free.clear();
+    }
+
+    /// Remove all items that do not satisfy a predicate.
+    /// ##### Performance
+    /// Removing elements does not require shifting elements but
+    /// does change the ordering of items.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///    let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// slotmap.retain(|(key, val)| val % 2 == 0);
+    ///
+    /// assert_eq!(slotmap.len(), 5);
+    /// ```
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: Fn((Key, &T)) -> bool,
+    {
+        self.retain_mut(|key, val| f((key, val)));
+    }
+
+    /// Remove all items that do not satisfy a predicate, which is also given
+    /// a mutable reference to each value so that kept values can be updated in
+    /// the same pass.
+    /// See [`SlotMap::retain`](crate::SlotMap::retain)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///    let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// slotmap.retain_mut(|_, val| {
+    ///     *val -= 1;
+    ///     *val >= 0
+    /// });
+    ///
+    /// assert_eq!(slotmap.len(), 9);
+    /// ```
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Key, &mut T) -> bool,
+    {
+        let mut i = 0;
+        while i < self.items.len() {
+            let key = self.items[i].key;
+            let val = &mut self.items[i].value;
+            if f(key, val) {
+                i += 1;
+            } else {
+                self.remove(key);
+            }
+        }
+    }
+
+    /// Remove all items that do not satisfy a predicate, returning the
+    /// removed values along with their now stale keys.
+    /// See [`SlotMap::retain`](crate::SlotMap::retain)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = (0..10).collect::<SlotMap<_>>();
+    /// let removed = slotmap.retain_with_removed(|_, val| val % 2 == 0);
+    ///
+    /// assert_eq!(slotmap.len(), 5);
+    /// assert_eq!(removed.len(), 5);
+    /// ```
+    pub fn retain_with_removed<F>(&mut self, mut f: F) -> Vec<(Key, T)>
+    where
+        F: FnMut(Key, &T) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.items.len() {
+            let key = self.items[i].key;
+            if f(key, &self.items[i].value) {
+                i += 1;
+            } else if let Some(value) = self.remove(key) {
+                removed.push((key, value));
+            }
+        }
+        removed
+    }
+
+    /// Returns an iterator that yields a (key, value) tuple for every
+    /// occupied slot in the slotmap.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///    let _ = slotmap.insert(i * 2);
+    /// }
+    ///
+    /// for (key, val) in &slotmap {
+    ///    println!("{:?}: {}", key, val);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(self.items.iter())
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// for (_key, val) in &mut slotmap {
+    ///     *val *= 2;
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut(self.items.iter_mut())
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    ///
+    /// Iterate over the slotmap in reverse storage order, which is newest
+    /// first until values are removed.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// assert_eq!(slotmap.iter_rev().next().map(|(_, val)| *val), Some(9));
+    /// ```
+    pub fn iter_rev(&self) -> Rev<Iter<'_, T>> {
+        self.iter().rev()
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    ///
+    /// Iterate over the slotmap in ascending key order, ordered by slot index
+    /// and then by generation. Unlike storage order, this is not affected by
+    /// removals.
+    /// ##### Performance
+    /// A vector holding a reference to every value is allocated and sorted
+    /// before the first item is yielded, so this is O(n log n) in time and
+    /// O(n) in space.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// let c = slotmap.insert("c");
+    /// slotmap.remove(a);
+    ///
+    /// let keys = slotmap.iter_sorted_by_key().map(|(key, _)| key).collect::<Vec<_>>();
+    /// assert_eq!(keys, [b, c]);
+    /// ```
+    pub fn iter_sorted_by_key(&self) -> impl Iterator<Item = (Key, &T)> {
+        let mut items = self.iter().collect::<Vec<_>>();
+        items.sort_unstable_by_key(|&(key, _)| key);
+        items.into_iter()
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    ///
+    /// Iterate over the slotmap in the order values were inserted, which
+    /// unlike storage order is not affected by removals. Requires the
+    /// `insertion_order` feature, which stores an extra `u64` with every
+    /// value.
+    /// ##### Performance
+    /// Values are sorted by their insertion sequence up front, with the same
+    /// cost as
+    /// [`SlotMap::iter_sorted_by_key`](crate::SlotMap::iter_sorted_by_key).
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// let c = slotmap.insert("c");
+    /// slotmap.remove(a);
+    ///
+    /// let keys = slotmap.iter_by_insertion().map(|(key, _)| key).collect::<Vec<_>>();
+    /// assert_eq!(keys, [b, c]);
+    /// ```
+    #[cfg(feature = "insertion_order")]
+    pub fn iter_by_insertion(&self) -> impl Iterator<Item = (Key, &T)> {
+        let mut items = self.items.iter().collect::<Vec<_>>();
+        items.sort_unstable_by_key(|item| item.seq);
+        items.into_iter().map(|item| (item.key, &item.value))
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    ///
+    /// Iterate over a contiguous window of the slotmap's storage, using the
+    /// same positions as
+    /// [`SlotMap::values_mut_indexed`](crate::SlotMap::values_mut_indexed).
+    /// The range is clamped to `len`, so out of bounds ranges yield fewer or
+    /// no items rather than panicking.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let slotmap = (0..10).collect::<SlotMap<_>>();
+    ///
+    /// for batch in (0..slotmap.len()).step_by(4) {
+    ///     let values = slotmap.values_in_range(batch..batch + 4);
+    ///     println!("{:?}", values.map(|(_, value)| value).collect::<Vec<_>>());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn values_in_range(&self, range: Range<usize>) -> Iter<'_, T> {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        Iter(self.items[start..end].iter())
+    }
+
+    /// Iterate over values in the slotmap.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// for value in slotmap.values() {
+    ///     println!("{value}");
+    /// }
+    #[must_use]
+    pub fn values(&self) -> Values<'_, T> {
+        Values(self.iter())
+    }
+
+    /// See [`SlotMap::values`](crate::SlotMap::values)
+    ///
+    /// Iterate over values in reverse storage order.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// assert_eq!(slotmap.values_rev().next(), Some(&9));
+    /// ```
+    pub fn values_rev(&self) -> Rev<Values<'_, T>> {
+        self.values().rev()
+    }
+
+    /// See [`SlotMap::values`](crate::SlotMap::values)
+    ///
+    /// Iterate over mutable references to values in the slotmap.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// for mut value in slotmap.values_mut() {
+    ///     *value += 1;
+    /// }
+    #[must_use]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, T> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// See [`SlotMap::values_mut`](crate::SlotMap::values_mut)
+    ///
+    /// Iterate over mutable references to values along with their keys and
+    /// their position in the slotmap's storage. Positions are contiguous from
+    /// `0` to `len`, follow the same order as
+    /// [`SlotMap::iter`](crate::SlotMap::iter), and are only stable until the
+    /// next removal.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// for (position, _, value) in slotmap.values_mut_indexed() {
+    ///     *value += position;
+    /// }
+    /// ```
+    pub fn values_mut_indexed(&mut self) -> impl Iterator<Item = (usize, Key, &mut T)> {
+        self.items
+            .iter_mut()
+            .enumerate()
+            .map(|(position, item)| (position, item.key, &mut item.value))
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    ///
+    /// Also yields the value associated with each key in a secondary map, or
+    /// `None` if the secondary map doesn't have one.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut names = SlotMap::new();
+    /// let mut ages = SecondaryMap::new();
+    /// let a = names.insert("alice");
+    /// let _ = names.insert("bob");
+    /// ages.insert(a, 30);
+    ///
+    /// for (_, name, age) in names.iter_with(&ages) {
+    ///     match age {
+    ///         Some(age) => println!("{name}: {age}"),
+    ///         None => println!("{name}: unknown"),
+    ///     }
+    /// }
+    /// ```
+    pub fn iter_with<'a, V>(
+        &'a self,
+        secondary: &'a SecondaryMap<V>,
+    ) -> impl Iterator<Item = (Key, &'a T, Option<&'a V>)> {
+        self.iter()
+            .map(|(key, value)| (key, value, secondary.get(key)))
+    }
+
+    /// See [`SlotMap::values`](crate::SlotMap::values)
+    ///
+    /// Consume slotmap and iterate over the keys.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// let values = slotmap.into_values().collect::<Vec<i32>>();
+    /// ```
+    #[must_use]
+    pub fn into_values(self) -> IntoValues<T> {
+        IntoValues(self.into_iter())
+    }
+
+    /// Iterate over keys in the slotmap.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// for key in slotmap.keys() {
+    ///     println!("{:?}", key);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn keys(&self) -> Keys<'_, T> {
+        Keys(self.iter())
+    }
+
+    /// See [`SlotMap::keys`](crate::SlotMap::keys)
+    ///
+    /// Collects every key in the slotmap into a vector, in the same order as
+    /// `keys`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let a = slotmap.insert("a");
+    /// let b = slotmap.insert("b");
+    /// assert_eq!(slotmap.key_vec(), [a, b]);
+    /// ```
+    #[must_use]
+    pub fn key_vec(&self) -> Vec<Key> {
+        let mut keys = Vec::with_capacity(self.len());
+        for item in &self.items {
+            keys.push(item.key);
+        }
+        keys
+    }
+
+    /// See [`SlotMap::keys`](crate::SlotMap::keys)
+    ///
+    /// Consume slotmap and iterate over the keys.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// let keys = slotmap.into_keys().collect::<Vec<_>>();
+    /// ```
+    #[must_use]
+    pub fn into_keys(self) -> IntoKeys<T> {
+        IntoKeys(self.into_iter())
+    }
+
+    /// Moves every value from `other` into this slotmap, leaving `other`
+    /// empty. Values are assigned new keys, so this returns a list of
+    /// `(old_key, new_key)` pairs that can be used to translate keys from
+    /// `other`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut a = SlotMap::new();
+    /// let mut b = SlotMap::new();
+    /// let _ = a.insert("a");
+    /// let key = b.insert("b");
+    ///
+    /// let remap = a.append(&mut b);
+    /// assert!(b.is_empty());
+    ///
+    /// let (old, new) = remap[0];
+    /// assert_eq!(old, key);
+    /// assert_eq!(a[new], "b");
+    /// ```
+    pub fn append(&mut self, other: &mut SlotMap<T>) -> Vec<(Key, Key)> {
+        self.reserve(other.len());
+        other
+            .drain()
+            .map(|(key, value)| (key, self.insert(value)))
+            .collect()
+    }
+
+    /// Moves every value that satisfies a predicate into a new slotmap. Moved
+    /// values are assigned new keys, so this also returns a list of
+    /// `(old_key, new_key)` pairs. Values that stay keep their keys.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///    let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// let (evens, remap) = slotmap.split_off(|_, val| val % 2 == 0);
+    ///
+    /// assert_eq!(slotmap.len(), 5);
+    /// assert_eq!(evens.len(), 5);
+    /// assert_eq!(remap.len(), 5);
+    /// ```
+    pub fn split_off<F>(&mut self, mut f: F) -> (SlotMap<T>, Vec<(Key, Key)>)
+    where
+        F: FnMut(Key, &T) -> bool,
+    {
+        let mut other = SlotMap::new();
+        let mut remap = Vec::new();
+        let mut i = 0;
+        while i < self.items.len() {
+            let key = self.items[i].key;
+            if !f(key, &self.items[i].value) {
+                i += 1;
+            } else if let Some(value) = self.remove(key) {
+                remap.push((key, other.insert(value)));
+            }
+        }
+        (other, remap)
+    }
+
+    /// Removes every value from the slotmap, yielding each one along with
+    /// its key. All keys become stale, but the allocated storage is kept for
+    /// reuse.
+    ///
+    /// Any values that aren't consumed are removed when the iterator is
+    /// dropped.
+    /// ##### Order
+    /// Values are taken from the end of the slotmap's storage, so they are
+    /// yielded in the reverse of the order
+    /// [`SlotMap::iter`](crate::SlotMap::iter) visits them in. This way no
+    /// value has to be moved to fill the gap left by a removed one.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let _ = slotmap.insert(i);
+    /// }
+    ///
+    /// let values = slotmap.drain().map(|(_, value)| value).collect::<Vec<_>>();
+    /// assert_eq!(values.len(), 10);
+    /// assert!(slotmap.is_empty());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain(self)
+    }
+
+    /// Returns an iterator that removes and yields a (key, value) tuple for
+    /// every value that satisfies a predicate, leaving the rest in place.
+    ///
+    /// Values are removed as the iterator is advanced. If the iterator is
+    /// dropped early, the predicate is still applied to the remaining values
+    /// and the ones that satisfy it are removed.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = (0..10).collect::<SlotMap<_>>();
+    /// let mut evens = slotmap
+    ///     .drain_filter(|_, val| *val % 2 == 0)
+    ///     .map(|(_, val)| val)
+    ///     .collect::<Vec<_>>();
+    /// evens.sort_unstable();
+    ///
+    /// assert_eq!(evens, [0, 2, 4, 6, 8]);
+    /// assert_eq!(slotmap.len(), 5);
+    /// ```
+    pub fn drain_filter<F>(&mut self, f: F) -> DrainFilter<'_, T, F>
+    where
+        F: FnMut(Key, &mut T) -> bool,
+    {
+        DrainFilter {
+            slotmap: self,
+            position: 0,
+            f,
+        }
+    }
+
+    /// Returns a cursor that walks the slotmap in storage order and can
+    /// remove values as it goes, starting at the first value.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = (0..10).collect::<SlotMap<_>>();
+    /// let mut cursor = slotmap.cursor_mut();
+    ///
+    /// while let Some((_, value)) = cursor.current() {
+    ///     if *value % 2 == 0 {
+    ///         cursor.remove_current();
+    ///     } else {
+    ///         cursor.advance();
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(slotmap.len(), 5);
+    /// ```
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            slotmap: self,
+            position: 0,
+        }
+    }
+
+    fn push_item(&mut self, key: Key, value: T) -> usize {
+        self.items.push(Item {
+            value,
+            key,
+            #[cfg(feature = "insertion_order")]
+            seq: self.next_seq,
+        });
+        #[cfg(feature = "insertion_order")]
+        {
+            self.next_seq += 1;
+        }
+        self.items.len() - 1
+    }
+
+    // Returns the error an insert would fail with if the slotmap had `slots`
+    // slots. The slot count is a parameter so that exhaustion can be tested
+    // without allocating the whole index space.
+    fn insert_error(&self, slots: usize) -> Option<fn(T) -> InsertError<T>> {
+        if self.max_capacity.is_some_and(|max| self.len() >= max) {
+            Some(InsertError::Full)
+        } else if self.free.is_empty() && Key::new(slots, self.fresh_generation, self.id).is_none()
+        {
+            Some(InsertError::Exhausted)
+        } else {
+            None
+        }
+    }
+
+    fn indirect_index(&self, key: Key) -> Option<usize> {
+        self.checked_indirect_index(key).ok()
+    }
+
+    fn checked_indirect_index(&self, key: Key) -> Result<usize, KeyError> {
+        match self.slots.get(key.index()).copied() {
+            Some(_) if !self.id.accepts(key.map_id) => Err(KeyError::WrongMap),
+            Some(Slot::Occupied(indirect_index))
+                if self
+                    .items
+                    .get(indirect_index)
+                    .is_some_and(|item| item.key.generation == key.generation) =>
+            {
+                Ok(indirect_index)
+            }
+            Some(_) => Err(KeyError::StaleGeneration),
+            None => Err(KeyError::OutOfRange),
+        }
+    }
+}
+
+impl<T> Index<Key> for SlotMap<T> {
+    type Output = T;
+    fn index(&self, index: Key) -> &Self::Output {
+        match self.get(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+impl<T> IndexMut<Key> for SlotMap<T> {
+    fn index_mut(&mut self, index: Key) -> &mut Self::Output {
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+#[cold]
+#[track_caller]
+pub(crate) fn invalid_key(key: Key) -> ! {
+    panic!(
+        "no value for key with index {} and generation {}",
+        key.index(),
+        key.generation()
+    )
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        SlotMap::new()
+    }
+}
+
+impl<T: Clone> Clone for SlotMap<T> {
+    fn clone(&self) -> Self {
+        SlotMap {
+            items: self.items.clone(),
+            slots: self.slots.clone(),
+            free: self.free.clone(),
+            fresh_generation: self.fresh_generation,
+            id: self.id,
+            max_capacity: self.max_capacity,
+            #[cfg(feature = "insertion_order")]
+            next_seq: self.next_seq,
+        }
+    }
+
+    /// Reuses the existing allocations of `self` where possible, which avoids
+    /// reallocating when repeatedly cloning into the same slotmap.
+    fn clone_from(&mut self, source: &Self) {
+        self.items.clone_from(&source.items);
+        self.slots.clone_from(&source.slots);
+        self.free.clone_from(&source.free);
+        self.fresh_generation = source.fresh_generation;
+        self.id = source.id;
+        self.max_capacity = source.max_capacity;
+        #[cfg(feature = "insertion_order")]
+        {
+            self.next_seq = source.next_seq;
+        }
+    }
+}
+
+impl<T: Debug> Debug for SlotMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for SlotMap<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| {
+                // Slotmaps with different ids can still hold equal values
+                // under equal slots, so compare without the id.
+                let key = Key {
+                    map_id: MapId::default(),
+                    ..key
+                };
+                other.get(key).is_some_and(|other| value == other)
+            })
+    }
+}
+
+impl<T: Eq> Eq for SlotMap<T> {}
+
+/// Hashes every key value pair in key order, so slotmaps that compare equal
+/// hash equally regardless of the order values are stored in. This sorts the
+/// pairs on every call, see
+/// [`SlotMap::iter_sorted_by_key`](crate::SlotMap::iter_sorted_by_key).
+impl<T: Hash> Hash for SlotMap<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for (key, value) in self.iter_sorted_by_key() {
+            key.index().hash(state);
+            key.generation().hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+/// Restores a serialized slotmap after checking that its internal state is
+/// consistent.
+/// ##### Checked keys
+/// With the `checked_keys` feature enabled the slotmap's id is restored as
+/// well, so that serialized keys keep resolving. If the slotmap it was
+/// serialized from is still alive, both share the same id and accept each
+/// other's keys.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for SlotMap<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw<T> {
+            items: Vec<Item<T>>,
+            slots: Vec<Slot>,
+            free: Vec<usize>,
+            #[serde(default)]
+            fresh_generation: Generation,
+            #[cfg_attr(not(feature = "checked_keys"), serde(skip))]
+            #[cfg_attr(feature = "checked_keys", serde(default))]
+            id: MapId,
+        }
+
+        let Raw {
+            items,
+            slots,
+            free,
+            fresh_generation,
+            id,
+        } = Raw::deserialize(deserializer)?;
+
+        for (i, item) in items.iter().enumerate() {
+            if slots.get(item.key.index()) != Some(&Slot::Occupied(i)) {
+                return Err(de::Error::custom("item does not match its slot"));
+            }
+        }
+
+        let occupied = slots
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Occupied(_)))
+            .count();
+        if occupied != items.len() {
+            return Err(de::Error::custom("slot does not match an item"));
+        }
+
+        let mut seen = vec![false; slots.len()];
+        for &index in &free {
+            if !matches!(slots.get(index), Some(Slot::Vacant(_))) || seen[index] {
+                return Err(de::Error::custom("invalid free slot"));
+            }
+            seen[index] = true;
+        }
+
+        #[cfg(feature = "insertion_order")]
+        let next_seq = items.iter().map(|item| item.seq + 1).max().unwrap_or(0);
+
+        Ok(SlotMap {
+            items,
+            slots,
+            free,
+            fresh_generation,
+            id,
+            max_capacity: None,
+            #[cfg(feature = "insertion_order")]
+            next_seq,
+        })
+    }
+}
+
+/// Iterates over the slotmap in parallel, see
+/// [`SlotMap::iter`](crate::SlotMap::iter).
+/// ##### Example
+/// ```
+/// use rayon::prelude::*;
+/// use slotmap::SlotMap;
+///
+/// let slotmap = (0..1000).collect::<SlotMap<u64>>();
+/// let sum: u64 = slotmap.par_iter().map(|(_, value)| value).sum();
+/// assert_eq!(sum, 499_500);
+/// ```
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> IntoParallelIterator for &'a SlotMap<T> {
+    type Item = IterItem<'a, T>;
+    type Iter = ParIter<'a, T>;
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter(self.items.as_slice().into_par_iter())
+    }
+}
+
+/// Iterates over the slotmap in parallel, see
+/// [`SlotMap::iter_mut`](crate::SlotMap::iter_mut).
+#[cfg(feature = "rayon")]
+impl<'a, T: Send> IntoParallelIterator for &'a mut SlotMap<T> {
+    type Item = IterMutItem<'a, T>;
+    type Iter = ParIterMut<'a, T>;
+    fn into_par_iter(self) -> Self::Iter {
+        ParIterMut(self.items.as_mut_slice().into_par_iter())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> ParallelIterator for ParIter<'a, T> {
+    type Item = IterItem<'a, T>;
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.0
+            .map(|item| (item.key, &item.value))
+            .drive_unindexed(consumer)
+    }
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Send> ParallelIterator for ParIterMut<'a, T> {
+    type Item = IterMutItem<'a, T>;
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.0
+            .map(|item| (item.key, &mut item.value))
+            .drive_unindexed(consumer)
+    }
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Sync> IndexedParallelIterator for ParIter<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.0.map(|item| (item.key, &item.value)).drive(consumer)
+    }
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        self.0
+            .map(|item| (item.key, &item.value))
+            .with_producer(callback)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Send> IndexedParallelIterator for ParIterMut<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.0
+            .map(|item| (item.key, &mut item.value))
+            .drive(consumer)
+    }
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        self.0
+            .map(|item| (item.key, &mut item.value))
+            .with_producer(callback)
+    }
+}
+
+impl<T> FromIterator<T> for SlotMap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut slotmap = SlotMap::new();
+        slotmap.extend(iter);
+        slotmap
+    }
+}
+
+impl<T> Extend<T> for SlotMap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.reserve(lower);
+        for value in iter {
+            let _ = self.insert(value);
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = IterItem<'a, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|item| (item.key, &item.value)).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = IterMutItem<'a, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .map(|item| (item.key, &mut item.value))
+            .next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> IntoIter<T> {
+    /// Returns the key and a reference to the value that the next call to
+    /// `next` will yield, without consuming it.
+    /// ##### Example
+    /// ```
+    /// use slotmap::SlotMap;
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let key = slotmap.insert("an example value");
+    ///
+    /// let mut iter = slotmap.into_iter();
+    /// assert_eq!(iter.peek(), Some((key, &"an example value")));
+    /// assert_eq!(iter.next(), Some((key, "an example value")));
+    /// assert_eq!(iter.peek(), None);
+    /// ```
+    #[must_use]
+    pub fn peek(&self) -> Option<(Key, &T)> {
+        self.0
+            .as_slice()
+            .first()
+            .map(|item| (item.key, &item.value))
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = IntoIterItem<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|item| (item.key, item.value)).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T> Iterator for Values<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T> Iterator for ValuesMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> Iterator for IntoValues<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> Iterator for Keys<'_, T> {
+    type Item = Key;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(key, _)| key).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> Iterator for IntoKeys<T> {
+    type Item = Key;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(key, _)| key).next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = IntoIterItem<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.0.items.last()?.key;
+        self.0.remove(key).map(|value| (key, value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<T, F> Iterator for DrainFilter<'_, T, F>
+where
+    F: FnMut(Key, &mut T) -> bool,
+{
+    type Item = IntoIterItem<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(item) = self.slotmap.items.get_mut(self.position) {
+            let key = item.key;
+            if (self.f)(key, &mut item.value) {
+                // The last item is swapped into this position, so it is
+                // visited next without advancing.
+                return self.slotmap.remove(key).map(|value| (key, value));
+            }
+            self.position += 1;
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.slotmap.len() - self.position))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .map(|item| (item.key, &item.value))
+            .next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for IterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .map(|item| (item.key, &mut item.value))
+            .next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .by_ref()
+            .map(|item| (item.key, item.value))
+            .next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for Values<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for ValuesMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoValues<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(_, value)| value).next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for Keys<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(key, _)| key).next_back()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoKeys<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().map(|(key, _)| key).next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for Values<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for ValuesMut<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoValues<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for Keys<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoKeys<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> ExactSizeIterator for Drain<'_, T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+impl<T> FusedIterator for IterMut<'_, T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+impl<T> FusedIterator for Values<'_, T> {}
+impl<T> FusedIterator for ValuesMut<'_, T> {}
+impl<T> FusedIterator for IntoValues<T> {}
+impl<T> FusedIterator for Keys<'_, T> {}
+impl<T> FusedIterator for IntoKeys<T> {}
+impl<T> FusedIterator for Drain<'_, T> {}
+impl<T, F> FusedIterator for DrainFilter<'_, T, F> where F: FnMut(Key, &mut T) -> bool {}
+
+impl<T> Drop for Drain<'_, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T, F> Drop for DrainFilter<'_, T, F>
+where
+    F: FnMut(Key, &mut T) -> bool,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T> CursorMut<'_, T> {
+    /// Returns the key and an exclusive reference to the value the cursor is
+    /// at, or `None` once the cursor has moved past the last value.
+    pub fn current(&mut self) -> Option<(Key, &mut T)> {
+        self.slotmap
+            .items
+            .get_mut(self.position)
+            .map(|item| (item.key, &mut item.value))
+    }
+
+    /// Moves the cursor to the next value.
+    pub fn advance(&mut self) {
+        self.position = (self.position + 1).min(self.slotmap.len());
+    }
+
+    /// Removes the value the cursor is at like
+    /// [`SlotMap::remove`](crate::SlotMap::remove). The value that is moved
+    /// into its place in storage becomes the current value, so no value is
+    /// skipped.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let key = self.slotmap.items.get(self.position)?.key;
+        self.slotmap.remove(key)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a SlotMap<T> {
+    type Item = IterItem<'a, T>;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut SlotMap<T> {
+    type Item = IterMutItem<'a, T>;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T> IntoIterator for SlotMap<T> {
+    type Item = IntoIterItem<T>;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.items.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_insert_get_remove() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        assert_eq!(*slotmap.get(a).unwrap(), "a");
+        assert_eq!(*slotmap.get(b).unwrap(), "b");
+        assert_eq!(*slotmap.get(c).unwrap(), "c");
+        assert_eq!(*slotmap.get_mut(a).unwrap(), "a");
+        assert_eq!(*slotmap.get_mut(b).unwrap(), "b");
+        assert_eq!(*slotmap.get_mut(c).unwrap(), "c");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        assert_eq!(slotmap.remove(a).unwrap(), "a");
+        assert_eq!(slotmap.remove(b).unwrap(), "b");
+        assert_eq!(slotmap.remove(c).unwrap(), "c");
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(slotmap.get(b), None);
+        assert_eq!(slotmap.get(c), None);
+    }
+
+    #[test]
+    fn test_len() {
+        let mut slotmap = SlotMap::new();
+        assert!(slotmap.is_empty());
+        let a = slotmap.insert(());
+        let b = slotmap.insert(());
+        let c = slotmap.insert(());
+        assert_eq!(slotmap.len(), 3);
+        slotmap.remove(a);
+        slotmap.remove(b);
+        slotmap.remove(c);
+        assert!(slotmap.is_empty());
+    }
+
+    #[test]
+    fn test_uaf() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        slotmap.remove(a);
+        let b = slotmap.insert("b");
+        assert_eq!(a.index, b.index);
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(*slotmap.get(b).unwrap(), "b");
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut slotmap = SlotMap::new();
+        let mut keys = Vec::new();
+        for x in 0..10 {
+            keys.push(slotmap.insert(x));
+        }
+        let mut it = slotmap.iter();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(
+            a.as_slice(),
+            [(_, 0), (_, 1), (_, 2), (_, 3), (_, 4)]
+        ));
+        assert!(matches!(
+            dbg!(b.as_slice()),
+            [(_, 9), (_, 8), (_, 7), (_, 6), (_, 5)]
+        ));
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut slotmap = SlotMap::new();
+        let mut keys = Vec::new();
+        for x in 0..10 {
+            keys.push(slotmap.insert(x));
+        }
+        for (_, val) in &mut slotmap {
+            *val *= 2;
+        }
+        let mut it = slotmap.iter();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(
+            a.as_slice(),
+            [(_, 0), (_, 2), (_, 4), (_, 6), (_, 8)]
+        ));
+        assert!(matches!(
+            dbg!(b.as_slice()),
+            [(_, 18), (_, 16), (_, 14), (_, 12), (_, 10)]
+        ));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut slotmap = SlotMap::new();
+        let mut keys = Vec::new();
+        for x in 0..10 {
+            keys.push(slotmap.insert(x));
+        }
+        let mut it = slotmap.into_iter();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(
+            a.as_slice(),
+            [(_, 0), (_, 1), (_, 2), (_, 3), (_, 4)]
+        ));
+        assert!(matches!(
+            dbg!(b.as_slice()),
+            [(_, 9), (_, 8), (_, 7), (_, 6), (_, 5)]
+        ));
+    }
+
+    #[test]
+    fn test_values() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        let mut it = slotmap.values();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
+        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+    }
+
+    #[test]
+    fn test_values_mut() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        let mut it = slotmap.values_mut();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
+        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+    }
+
+    #[test]
+    fn test_into_values() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        let mut it = slotmap.into_values();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().collect::<Vec<_>>();
+        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
+        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut slotmap = SlotMap::new();
+        let mut keys = Vec::new();
+        for x in 0..10 {
+            keys.push(slotmap.insert(x));
+        }
+        let mut it = slotmap.keys();
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.by_ref().rev().take(5).collect::<Vec<_>>();
+        assert_eq!(keys[0], a[0]);
+        assert_eq!(keys[1], a[1]);
+        assert_eq!(keys[2], a[2]);
+        assert_eq!(keys[3], a[3]);
+        assert_eq!(keys[4], a[4]);
+        assert_eq!(keys[9], b[0]);
+        assert_eq!(keys[8], b[1]);
+        assert_eq!(keys[7], b[2]);
+        assert_eq!(keys[6], b[3]);
+        assert_eq!(keys[5], b[4]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut slotmap = SlotMap::new();
+
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+
+        slotmap.retain(|(_, _)| true);
+        assert_eq!(slotmap.len(), 10);
+
+        slotmap.retain(|(_, val)| *val >= 5);
+        assert_eq!(slotmap.len(), 5);
+
+        slotmap.retain(|(_, _)| false);
+        assert!(slotmap.is_empty());
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        slotmap.remove(b);
+        let debug = format!("{slotmap:?}");
+        assert!(debug.contains(&format!("{a:?}: \"a\"")));
+        assert!(debug.contains(&format!("{c:?}: \"c\"")));
+        assert!(!debug.contains("\"b\""));
+        assert!(!debug.contains("Slot"));
+        assert!(!debug.contains("Item"));
+    }
+
+    #[test]
+    fn test_slot_reuse() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        slotmap.remove(a);
+        slotmap.remove(c);
+        let a = slotmap.insert("d");
+        let c = slotmap.insert("e");
+        assert_eq!(slotmap[a], "d");
+        assert_eq!(slotmap[b], "b");
+        assert_eq!(slotmap[c], "e");
+        assert_eq!(slotmap.len(), 3);
+    }
+
+    #[test]
+    fn test_eq() {
+        let mut a = SlotMap::new();
+        let key = a.insert(0);
+        a.remove(key);
+        for x in 0..3 {
+            let _ = a.insert(x);
+        }
+
+        let mut b = SlotMap::new();
+        let keys = (0..3).map(|x| b.insert(x)).collect::<Vec<_>>();
+        b.remove(keys[0]);
+        let _ = b.insert(0);
+
+        assert!(a == b);
+
+        b[keys[1]] = 10;
+        assert!(a != b);
+    }
+
+    #[test]
+    fn test_ne_generation() {
+        let mut a = SlotMap::new();
+        let _ = a.insert(0);
+
+        let mut b = SlotMap::new();
+        let key = b.insert(0);
+        b.remove(key);
+        let _ = b.insert(0);
+
+        assert!(a != b);
+    }
+
+    #[test]
+    fn test_ne_len() {
+        let mut a = SlotMap::new();
+        let _ = a.insert(0);
+
+        let mut b = a.clone();
+        let _ = b.insert(1);
+
+        assert!(a != b);
+        assert!(b != a);
+    }
+
+    #[test]
+    fn test_remove_no_recycle() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        assert_eq!(slotmap.remove_no_recycle(a), Some("a"));
+        assert_eq!(slotmap.remove_no_recycle(a), None);
+        let c = slotmap.insert("c");
+        assert_eq!(c.index, 2);
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(slotmap[b], "b");
+        assert_eq!(slotmap[c], "c");
+    }
+
+    #[test]
+    fn test_key_ord() {
+        use std::collections::BTreeSet;
+
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(());
+        let b = slotmap.insert(());
+        slotmap.remove(a);
+        let c = slotmap.insert(());
+        let set = [c, b, a].into_iter().collect::<BTreeSet<_>>();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), [a, c, b]);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let slotmap = (0..100).collect::<SlotMap<i32>>();
+        assert_eq!(slotmap.len(), 100);
+        let mut values = slotmap.values().copied().collect::<Vec<_>>();
+        values.sort_unstable();
+        assert!(values.into_iter().eq(0..100));
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(0);
+        let b = slotmap.insert(1);
+        slotmap.remove(a);
+        slotmap.extend(vec![2, 3, 4]);
+        assert_eq!(slotmap.len(), 4);
+        assert_eq!(slotmap[b], 1);
+        let mut values = slotmap.values().copied().collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_get_disjoint_mut() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        let c = slotmap.insert(3);
+        for value in slotmap.get_disjoint_mut([c, a, b]).unwrap() {
+            *value *= 10;
+        }
+        assert_eq!(slotmap[a], 10);
+        assert_eq!(slotmap[b], 20);
+        assert_eq!(slotmap[c], 30);
+    }
+
+    #[test]
+    fn test_get_disjoint_mut_aliasing() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        assert!(slotmap.get_disjoint_mut([a, b, a]).is_none());
+    }
+
+    #[test]
+    fn test_get_disjoint_mut_stale() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        slotmap.remove(a);
+        let _ = slotmap.insert(3);
+        assert!(slotmap.get_disjoint_mut([a, b]).is_none());
+    }
+
+    #[test]
+    fn test_insert_with_key() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert_with_key(|key| (key, "a"));
+        slotmap.remove(a);
+        let b = slotmap.insert_with_key(|key| (key, "b"));
+        assert_eq!(a.index, b.index);
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(slotmap.get(b), Some(&(b, "b")));
+    }
+
+    #[test]
+    fn test_get_or_insert_with_live() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        assert_eq!(slotmap.get_or_insert_with(a, || 0), Some(&mut 1));
+        assert_eq!(slotmap.len(), 1);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_vacant() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        slotmap.remove(a);
+        let next = Key {
+            generation: a.generation.next().unwrap(),
+            ..a
+        };
+        assert_eq!(slotmap.get_or_insert_with(next, || 3), Some(&mut 3));
+        assert_eq!(slotmap.len(), 2);
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(slotmap[next], 3);
+        assert_eq!(slotmap[b], 2);
+        let c = slotmap.insert(4);
+        assert_eq!(c.index, 2);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_stale() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        slotmap.remove(a);
+        assert_eq!(slotmap.get_or_insert_with(a, || 2), None);
+        let b = slotmap.insert(3);
+        assert_eq!(slotmap.get_or_insert_with(a, || 4), None);
+        slotmap.remove_no_recycle(b);
+        let retired = Key {
+            generation: b.generation.next().unwrap(),
+            ..b
+        };
+        assert_eq!(slotmap.get_or_insert_with(retired, || 5), None);
+        assert!(slotmap.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_get_or_insert_with_out_of_range() {
+        let mut slotmap = SlotMap::new();
+        let mut other = SlotMap::new();
+        let key = other.insert(1);
+        slotmap.get_or_insert_with(key, || 0);
+    }
+
+    #[test]
+    fn test_get_key_value() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        assert_eq!(slotmap.get_key_value(a), Some((a, &"a")));
+        assert_eq!(slotmap.get_key_value(b), Some((b, &"b")));
+        slotmap.remove(a);
+        assert_eq!(slotmap.get_key_value(a), None);
+    }
+
+    #[test]
+    fn test_exact_size() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        assert_eq!(slotmap.iter().len(), slotmap.len());
+        assert_eq!(slotmap.values().len(), slotmap.len());
+        assert_eq!(slotmap.keys().len(), slotmap.len());
+        assert_eq!(slotmap.iter_mut().len(), 10);
+        assert_eq!(slotmap.values_mut().len(), 10);
+        let mut it = slotmap.clone().into_iter();
+        it.next();
+        it.next_back();
+        assert_eq!(it.len(), 8);
+        assert_eq!(slotmap.into_values().len(), 10);
+    }
+
+    #[test]
+    fn test_fused() {
+        fn assert_fused<I: FusedIterator>(_: I) {}
+
+        let mut slotmap = SlotMap::<i32>::new();
+        assert_fused(slotmap.iter());
+        assert_fused(slotmap.iter_mut());
+        assert_fused(slotmap.values());
+        assert_fused(slotmap.values_mut());
+        assert_fused(slotmap.keys());
+        assert_fused(slotmap.clone().into_values());
+        assert_fused(slotmap.into_iter());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        slotmap.remove(keys[0]);
+        slotmap.remove(keys[5]);
+        slotmap.remove(keys[9]);
+        slotmap.shrink_to_fit();
+
+        let json = serde_json::to_string(&slotmap).unwrap();
+        let mut deserialized: SlotMap<i32> = serde_json::from_str(&json).unwrap();
+        assert!(deserialized == slotmap);
+        for &key in &keys {
+            assert_eq!(deserialized.get(key), slotmap.get(key));
+        }
+        for x in 10..13 {
+            assert_eq!(deserialized.insert(x), slotmap.insert(x));
+        }
+        assert_eq!(deserialized.get(keys[9]), None);
+
+        let json = serde_json::to_string(&keys[1]).unwrap();
+        assert_eq!(serde_json::from_str::<Key>(&json).unwrap(), keys[1]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_invalid() {
+        let json = r#"{"items":[],"slots":[{"Occupied":0}],"free":[]}"#;
+        assert!(serde_json::from_str::<SlotMap<i32>>(json).is_err());
+        let json = r#"{"items":[],"slots":[{"Vacant":1}],"free":[0,0]}"#;
+        assert!(serde_json::from_str::<SlotMap<i32>>(json).is_err());
+    }
+
+    #[test]
+    fn test_ffi() {
+        for index in [0, 1, 1000, u32::MAX] {
+            for generation in [0, 1, 1000, u32::MAX] {
+                let value = u64::from(index) | (u64::from(generation) << 32);
+                let key = Key::from_ffi(value);
+                assert_eq!(key.is_null(), index == u32::MAX);
+                if !key.is_null() {
+                    assert_eq!(key.index(), index as usize);
+                }
+                assert_eq!(key.generation(), u64::from(generation));
+                assert_eq!(key.as_ffi(), value);
+            }
+        }
+        assert_eq!(Key::from_ffi(Key::null().as_ffi()), Key::null());
+    }
+
+    #[cfg(not(feature = "key32"))]
+    #[test]
+    fn test_ffi_truncation() {
+        let key = Key::new(u32::MAX as usize + 1, Generation(0), MapId::default()).unwrap();
+        assert_ne!(Key::from_ffi(key.as_ffi()), key);
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        let order = slotmap.keys().rev().collect::<Vec<_>>();
+        let mut drained = slotmap.drain().collect::<Vec<_>>();
+        assert!(drained.iter().map(|&(key, _)| key).eq(order));
+        drained.sort_unstable();
+        assert!(drained.into_iter().eq(keys.iter().copied().zip(0..10)));
+        assert!(slotmap.is_empty());
+        assert!(keys.iter().all(|&key| slotmap.get(key).is_none()));
+    }
+
+    #[test]
+    fn test_drain_partial() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        let mut drain = slotmap.drain();
+        assert_eq!(drain.len(), 10);
+        drain.next();
+        drain.next();
+        assert_eq!(drain.len(), 8);
+        drop(drain);
+        assert!(slotmap.is_empty());
+        assert!(keys.iter().all(|&key| slotmap.get(key).is_none()));
+    }
+
+    #[test]
+    fn test_drain_capacity() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        let capacity = slotmap.items.capacity();
+        slotmap.drain();
+        assert_eq!(slotmap.items.capacity(), capacity);
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        assert_eq!(slotmap.slots.len(), 10);
+    }
+
+    #[test]
+    fn test_get2_mut() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        let (x, y) = slotmap.get2_mut(a, b).unwrap();
+        std::mem::swap(x, y);
+        assert_eq!(slotmap[a], 2);
+        assert_eq!(slotmap[b], 1);
+    }
+
+    #[test]
+    fn test_get2_mut_same_key() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        assert!(slotmap.get2_mut(a, a).is_none());
+    }
+
+    #[test]
+    fn test_get2_mut_stale() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        slotmap.remove(b);
+        assert!(slotmap.get2_mut(a, b).is_none());
+        assert!(slotmap.get2_mut(b, a).is_none());
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        assert!(slotmap.swap(a, b));
+        assert_eq!(slotmap.get(a), Some(&"b"));
+        assert_eq!(slotmap.get(b), Some(&"a"));
+        assert!(slotmap.swap(a, a));
+        assert_eq!(slotmap.get(a), Some(&"b"));
+        slotmap.remove(b);
+        assert!(!slotmap.swap(a, b));
+        assert!(!slotmap.swap(b, b));
+        assert_eq!(slotmap.get(a), Some(&"b"));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        assert_eq!(slotmap.replace(a, "b"), Some("a"));
+        assert_eq!(slotmap[a], "b");
+        slotmap.remove(a);
+        assert_eq!(slotmap.replace(a, "c"), None);
+        assert!(slotmap.is_empty());
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..100).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        for &key in keys[10..].iter().chain(&keys[..5]) {
+            slotmap.remove(key);
+        }
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.slots.len(), 10);
+        assert!(slotmap.slots.capacity() < 100);
+        assert!(slotmap.items.capacity() < 100);
+        assert_eq!(slotmap.free.len(), 5);
+        for (x, &key) in keys.iter().enumerate().skip(5).take(5) {
+            assert_eq!(slotmap[key], x);
+        }
+        let a = slotmap.insert(100);
+        assert!(a.index < 5);
+        for _ in 0..5 {
+            let _ = slotmap.insert(0);
+        }
+        assert_eq!(slotmap.slots.len(), 11);
+
+        slotmap.retain(|_| false);
+        slotmap.shrink_to_fit();
+        assert!(slotmap.slots.is_empty());
+        assert!(slotmap.free.is_empty());
+    }
+
+    #[test]
+    fn test_key_accessors() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(());
+        assert_eq!(a.index(), 0);
+        assert_eq!(a.generation(), 0);
+        slotmap.remove(a);
+        let b = slotmap.insert(());
+        assert_eq!(b.index(), 0);
+        assert_eq!(b.generation(), 1);
+    }
+
+    #[test]
+    fn test_null_key() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let null = Key::null();
+        assert!(null.is_null());
+        assert!(!a.is_null());
+        assert_eq!(slotmap.get(null), None);
+        assert_eq!(slotmap.get_mut(null), None);
+        assert_eq!(slotmap.remove(null), None);
+        assert!(!slotmap.contains_key(null));
+    }
+
+    #[test]
+    fn test_generation_overflow() {
+        let mut slotmap = SlotMap::new();
+        let _ = slotmap.insert(0);
+        slotmap.items[0].key.generation = Generation(RawGeneration::MAX);
+        let a = slotmap.items[0].key;
+        assert_eq!(slotmap.remove(a), Some(0));
+        assert_eq!(slotmap.get(a), None);
+        assert!(slotmap.free.is_empty());
+        let b = slotmap.insert(1);
+        assert_eq!(b.index, 1);
+        assert_eq!(slotmap.get(a), None);
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.slots.len(), 2);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_stale_keys() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..3);
+        let a = slotmap.reissue(keys[2]).unwrap();
+        slotmap.remove(keys[1]);
+        slotmap.remove(a);
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.slots.len(), 1);
+        let mut cloned = slotmap.clone();
+        for map in [&mut slotmap, &mut cloned] {
+            let b = map.insert(3);
+            let c = map.insert(4);
+            assert_eq!((b.index(), c.index()), (1, 2));
+            assert!(b.generation() > keys[1].generation());
+            assert!(c.generation() > a.generation());
+            for key in [keys[1], keys[2], a] {
+                assert_eq!(map.get(key), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink_to_fit_retired() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(0);
+        let b = slotmap.insert(1);
+        slotmap.remove_no_recycle(b);
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.slots.len(), 2);
+        let c = slotmap.insert(2);
+        assert_eq!(c.index, 2);
+        assert_eq!(slotmap[a], 0);
+    }
+
+    #[cfg(all(feature = "key32", not(feature = "checked_keys")))]
+    #[test]
+    fn test_key32_size() {
+        assert_eq!(std::mem::size_of::<Key>(), 8);
+    }
+
+    #[test]
+    fn test_get_unchecked() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        slotmap.remove(a);
+        assert!(slotmap.contains_key(b));
+        assert_eq!(unsafe { *slotmap.get_unchecked(b) }, 2);
+        unsafe { *slotmap.get_unchecked_mut(b) += 1 };
+        assert_eq!(slotmap[b], 3);
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let mut slotmap = SlotMap::with_max_capacity(3);
+        let keys = (0..3)
+            .map(|x| slotmap.try_insert(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(slotmap.try_insert(3), Err(InsertError::Full(3)));
+        assert_eq!(slotmap.len(), 3);
+        slotmap.remove(keys[1]);
+        let key = slotmap.try_insert(4).unwrap();
+        assert_eq!(slotmap[key], 4);
+        assert_eq!(slotmap.try_insert(5), Err(InsertError::Full(5)));
+        assert_eq!(slotmap.try_insert(5).unwrap_err().into_inner(), 5);
+        slotmap.set_max_capacity(4);
+        assert!(slotmap.try_insert(5).is_ok());
+    }
+
+    #[test]
+    fn test_try_insert_unlimited() {
+        let mut slotmap = SlotMap::new();
+        for x in 0..100 {
+            assert!(slotmap.try_insert(x).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        slotmap.remove(keys[0]);
+        let removed = slotmap.remove_many(&[keys[0], keys[1], keys[2], keys[1], keys[9]]);
+        assert_eq!(removed, 3);
+        assert_eq!(slotmap.len(), 6);
+        assert!(keys[3..9].iter().all(|&key| slotmap.contains_key(key)));
+    }
+
+    #[test]
+    fn test_retain_mut() {
+        let mut slotmap = SlotMap::new();
+        let keys = (1..=10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+
+        slotmap.retain_mut(|_, ttl| {
+            *ttl -= 1;
+            *ttl > 0
+        });
+
+        assert_eq!(slotmap.len(), 9);
+        assert!(slotmap.get(keys[0]).is_none());
+        for (x, &key) in keys.iter().enumerate().skip(1) {
+            assert_eq!(slotmap[key], x);
+        }
+    }
+
+    #[test]
+    fn test_first_last_key_value() {
+        let mut slotmap = SlotMap::new();
+        assert_eq!(slotmap.first_key_value(), None);
+        assert_eq!(slotmap.last_key_value(), None);
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        assert_eq!(slotmap.first_key_value(), Some((a, &"a")));
+        assert_eq!(slotmap.last_key_value(), Some((c, &"c")));
+        slotmap.remove(a);
+        assert_eq!(slotmap.first_key_value(), Some((c, &"c")));
+        assert_eq!(slotmap.last_key_value(), Some((b, &"b")));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut a = SlotMap::new();
+        let mut b = SlotMap::new();
+        let a_keys = (0..5).map(|x| a.insert(x)).collect::<Vec<_>>();
+        let b_keys = (5..10).map(|x| b.insert(x)).collect::<Vec<_>>();
+        b.remove(b_keys[0]);
+
+        let remap = a.append(&mut b);
+        assert!(b.is_empty());
+        assert_eq!(a.len(), 9);
+        assert_eq!(remap.len(), 4);
+        for (x, &key) in a_keys.iter().enumerate() {
+            assert_eq!(a[key], x);
+        }
+        for (old, new) in remap {
+            let x = b_keys.iter().position(|&key| key == old).unwrap();
+            assert_eq!(a[new], x + 5);
+        }
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+
+        let (evens, remap) = slotmap.split_off(|_, val| val % 2 == 0);
+
+        assert_eq!(slotmap.len(), 5);
+        assert_eq!(evens.len(), 5);
+        for (x, &key) in keys.iter().enumerate() {
+            if x % 2 == 0 {
+                assert!(slotmap.get(key).is_none());
+                let &(_, new) = remap.iter().find(|(old, _)| *old == key).unwrap();
+                assert_eq!(evens[new], x);
+            } else {
+                assert_eq!(slotmap[key], x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_keys() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        let mut it = slotmap.into_keys();
+        assert_eq!(it.len(), 10);
+        let a = it.by_ref().take(5).collect::<Vec<_>>();
+        let b = it.rev().collect::<Vec<_>>();
+        assert_eq!(a, keys[..5]);
+        assert!(b.into_iter().eq(keys[5..].iter().rev().copied()));
+    }
+
+    #[test]
+    fn test_get_each_mut() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        let values = slotmap.get_each_mut(&[keys[7], keys[2], keys[5]]).unwrap();
+        assert_eq!(
+            values.iter().map(|value| **value).collect::<Vec<_>>(),
+            [7, 2, 5]
+        );
+        for value in values {
+            *value += 10;
+        }
+        assert_eq!(slotmap[keys[7]], 17);
+        assert_eq!(slotmap[keys[2]], 12);
+        assert_eq!(slotmap[keys[5]], 15);
+        assert!(slotmap.get_each_mut(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_each_mut_duplicate() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        assert!(slotmap.get_each_mut(&[keys[1], keys[3], keys[1]]).is_none());
+    }
+
+    #[test]
+    fn test_get_each_mut_stale() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        slotmap.remove(keys[3]);
+        assert!(slotmap.get_each_mut(&[keys[1], keys[3]]).is_none());
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        let mut slotmap = SlotMap::new();
+        assert_eq!(slotmap.capacity(), 0);
+        assert!(slotmap.try_reserve(10).is_ok());
+        assert!(slotmap.capacity() >= 10);
+        let capacity = slotmap.capacity();
+        for x in 0..10 {
+            let _ = slotmap.insert(x);
+        }
+        assert_eq!(slotmap.capacity(), capacity);
+        assert!(slotmap.try_reserve(usize::MAX).is_err());
+        assert_eq!(slotmap.capacity(), capacity);
+        assert_eq!(slotmap.len(), 10);
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        slotmap.remove(keys[3]);
+        let mut forward = slotmap.iter().collect::<Vec<_>>();
+        forward.reverse();
+        assert_eq!(slotmap.iter_rev().collect::<Vec<_>>(), forward);
+        assert!(slotmap
+            .values_rev()
+            .eq(forward.into_iter().map(|(_, value)| value)));
+    }
+
+    #[test]
+    fn test_reissue() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.reissue(a).unwrap();
+        assert_eq!(c.index, a.index);
+        assert_eq!(slotmap.get(a), None);
+        assert_eq!(slotmap.get(c), Some(&"a"));
+        assert_eq!(slotmap.get(b), Some(&"b"));
+        assert_eq!(slotmap.reissue(a), None);
+        assert_eq!(slotmap.keys().collect::<Vec<_>>(), [c, b]);
+        assert_eq!(slotmap.remove(c), Some("a"));
+        let d = slotmap.insert("d");
+        assert_eq!(d.index, a.index);
+        assert_eq!(slotmap.get(c), None);
+    }
+
+    #[test]
+    fn test_key_matches() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        assert!(a.matches(0, 0));
+        assert!(b.matches(1, 0));
+        assert!(!a.matches(1, 0));
+        slotmap.remove(a);
+        let c = slotmap.insert("c");
+        assert!(c.matches(0, 1));
+        assert!(!Key::null().matches(0, 0));
+    }
+
+    #[test]
+    fn test_iter_sorted_by_key() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..5).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        slotmap.remove(keys[2]);
+        assert_ne!(
+            slotmap.keys().collect::<Vec<_>>(),
+            [keys[0], keys[1], keys[3], keys[4]]
+        );
+        let sorted = slotmap.iter_sorted_by_key().collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            [(keys[0], &0), (keys[1], &1), (keys[3], &3), (keys[4], &4)]
+        );
+        let c = slotmap.insert(5);
+        assert!(slotmap
+            .iter_sorted_by_key()
+            .map(|(key, _)| key)
+            .eq([keys[0], keys[1], c, keys[3], keys[4]]));
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key with index 1 and generation 0")]
+    fn test_index_stale_key() {
+        let mut slotmap = SlotMap::new();
+        let _ = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        slotmap.remove(b);
+        let _ = slotmap[b];
+    }
+
+    #[test]
+    #[should_panic(expected = "index 1")]
+    fn test_index_mut_stale_key() {
+        let mut slotmap = SlotMap::new();
+        let _ = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        slotmap.remove(b);
+        slotmap[b] = "c";
+    }
+
+    #[test]
+    fn test_clone_from() {
+        let mut source = SlotMap::new();
+        let keys = (0..10).map(|x| source.insert(x)).collect::<Vec<_>>();
+        source.remove(keys[3]);
+        let mut snapshot = SlotMap::new();
+        snapshot.reserve(100);
+        let capacity = snapshot.capacity();
+        snapshot.clone_from(&source);
+        assert_eq!(snapshot, source);
+        assert!(snapshot.capacity() >= capacity);
+        assert_eq!(snapshot.insert(10), source.insert(10));
+        let clone = source.clone();
+        assert_eq!(clone, source);
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(100);
+        let _ = slotmap.insert(101);
+        slotmap.remove(a);
+        let keys = slotmap.insert_many(0..10);
+        assert_eq!(keys.len(), 10);
+        assert_eq!(keys[0].index(), a.index());
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(slotmap[key], i);
+        }
+        assert_eq!(slotmap.len(), 11);
+        assert!(slotmap.insert_many(std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn test_key_default() {
+        #[derive(Default)]
+        struct Node {
+            parent: Key,
+        }
+
+        let mut slotmap = SlotMap::new();
+        let _ = slotmap.insert("a");
+        let node = Node::default();
+        assert!(node.parent.is_null());
+        assert_eq!(node.parent, Key::null());
+        assert!(slotmap.get(Key::default()).is_none());
+    }
+
+    #[test]
+    fn test_get_checked() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        assert_eq!(slotmap.get_checked(a), Ok(&1));
+        *slotmap.get_checked_mut(b).unwrap() += 1;
+        assert_eq!(slotmap.get_checked(b), Ok(&3));
+        slotmap.remove(a);
+        assert_eq!(slotmap.get_checked(a), Err(KeyError::StaleGeneration));
+        assert_eq!(slotmap.get_checked_mut(a), Err(KeyError::StaleGeneration));
+        let c = slotmap.insert(4);
+        assert_eq!(slotmap.get_checked(a), Err(KeyError::StaleGeneration));
+        assert_eq!(slotmap.get_checked(c), Ok(&4));
+        let mut other = SlotMap::new();
+        let keys = (0..5).map(|x| other.insert(x)).collect::<Vec<_>>();
+        assert_eq!(slotmap.get_checked(keys[4]), Err(KeyError::OutOfRange));
+        assert_eq!(slotmap.get_checked(Key::null()), Err(KeyError::OutOfRange));
+        assert_eq!(
+            KeyError::OutOfRange.to_string(),
+            "key index is out of range"
+        );
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut slotmap = SlotMap::new();
+        assert_eq!(slotmap.memory_usage(), 0);
+        let _ = slotmap.insert(0u64);
+        let usage = slotmap.memory_usage();
+        assert!(usage > 0);
+        slotmap.insert_many(1..100);
+        assert!(slotmap.memory_usage() > usage);
+        slotmap.reserve(100);
+        let usage = slotmap.memory_usage();
+        slotmap.insert_many(0..100);
+        assert_eq!(slotmap.memory_usage(), usage);
+    }
+
+    #[test]
+    fn test_values_mut_indexed() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..10);
+        slotmap.remove(keys[2]);
+        slotmap.remove(keys[5]);
+        let order = slotmap.keys().collect::<Vec<_>>();
+        let mut positions = Vec::new();
+        for (position, key, value) in slotmap.values_mut_indexed() {
+            assert_eq!(order[position], key);
+            *value *= 10;
+            positions.push(position);
+        }
+        assert_eq!(positions, (0..slotmap.len()).collect::<Vec<_>>());
+        assert_eq!(slotmap[keys[9]], 90);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_par_iter() {
+        use rayon::prelude::*;
+
+        let mut slotmap = (0..10_000u64).collect::<SlotMap<_>>();
+        let keys = slotmap.keys().step_by(7).collect::<Vec<_>>();
+        for key in keys {
+            slotmap.remove(key);
+        }
+        let serial: u64 = slotmap.values().sum();
+        let parallel: u64 = slotmap.par_iter().map(|(_, value)| value).sum();
+        assert_eq!(parallel, serial);
+        slotmap.par_iter_mut().for_each(|(_, value)| *value *= 2);
+        assert_eq!(slotmap.values().sum::<u64>(), serial * 2);
+        let pairs = slotmap
+            .par_iter()
+            .map(|(key, &value)| (key, value))
+            .collect::<Vec<_>>();
+        assert!(pairs
+            .into_iter()
+            .eq(slotmap.iter().map(|(key, &value)| (key, value))));
+    }
+
+    #[test]
+    fn test_values_in_range() {
+        let mut slotmap = (0..10).collect::<SlotMap<_>>();
+        let all = slotmap.iter().collect::<Vec<_>>();
+        assert_eq!(slotmap.values_in_range(2..5).collect::<Vec<_>>(), all[2..5]);
+        assert_eq!(slotmap.values_in_range(8..20).collect::<Vec<_>>(), all[8..]);
+        assert_eq!(slotmap.values_in_range(20..30).count(), 0);
+        assert_eq!(
+            slotmap.values_in_range(Range { start: 5, end: 2 }).count(),
+            0
+        );
+        let key = slotmap.keys().next().unwrap();
+        slotmap.remove(key);
+        assert_eq!(
+            slotmap
+                .values_in_range(0..2)
+                .map(|(_, &value)| value)
+                .collect::<Vec<_>>(),
+            [9, 1]
+        );
+    }
+
+    #[test]
+    fn test_from_raw_parts() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..10);
+        slotmap.remove(keys[4]);
+        slotmap.remove(keys[9]);
+        slotmap.remove(keys[1]);
+        slotmap.remove_no_recycle(keys[6]);
+        let _ = slotmap.insert(10);
+        let (pairs, vacant) = slotmap.clone().into_raw_parts();
+        assert_eq!(vacant.len(), 2);
+        let mut rebuilt = SlotMap::from_raw_parts(pairs.clone(), vacant.clone()).unwrap();
+        assert_eq!(rebuilt, slotmap);
+        assert_eq!(rebuilt.clone().into_raw_parts().0, pairs);
+        for x in 11..14 {
+            assert_eq!(rebuilt.insert(x).index(), slotmap.insert(x).index());
+        }
+        for &key in &keys {
+            assert_eq!(rebuilt.get(key), slotmap.get(key));
+        }
+        assert_eq!(rebuilt.get(keys[6]), None);
+        assert_eq!(rebuilt.get(keys[9]), None);
+        let mut duplicate = pairs.clone();
+        duplicate.push(pairs[0]);
+        assert!(SlotMap::from_raw_parts(duplicate, Vec::new()).is_none());
+        assert!(SlotMap::from_raw_parts(pairs.clone(), vec![pairs[0].0]).is_none());
+        assert!(SlotMap::from_raw_parts(vec![(Key::null(), 0)], Vec::new()).is_none());
+        assert!(SlotMap::<()>::from_raw_parts(Vec::new(), Vec::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_from_raw_parts_shrunk() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..3);
+        slotmap.remove(keys[2]);
+        slotmap.remove(keys[1]);
+        slotmap.shrink_to_fit();
+        let (pairs, vacant) = slotmap.into_raw_parts();
+        let mut rebuilt = SlotMap::from_raw_parts(pairs, vacant).unwrap();
+        let _ = rebuilt.insert(3);
+        let _ = rebuilt.insert(4);
+        assert_eq!(rebuilt.len(), 3);
+        assert_eq!(rebuilt[keys[0]], 0);
+        assert_eq!(rebuilt.get(keys[1]), None);
+        assert_eq!(rebuilt.get(keys[2]), None);
+    }
+
+    #[test]
+    #[cfg(feature = "checked_keys")]
+    fn test_checked_keys() {
+        let mut a = SlotMap::new();
+        let mut b = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let x = a.insert("x");
+        let y = b.insert("y");
+        assert_eq!((x.index(), x.generation()), (y.index(), y.generation()));
+        assert_ne!(x, y);
+        assert_eq!(a.get(y), None);
+        assert_eq!(b.get(x), None);
+        assert_eq!(a.get_checked(y), Err(KeyError::WrongMap));
+        assert_eq!(a.remove(y), None);
+        assert_eq!(a.len(), 1);
+        secondary.insert(x, 1);
+        assert_eq!(secondary.get(y), None);
+        assert_eq!(secondary.iter().next(), Some((x, &1)));
+        let clone = a.clone();
+        assert_eq!(clone[x], "x");
+        let unchecked = Key::from_ffi(x.as_ffi());
+        assert_eq!(a[unchecked], "x");
+        assert_eq!(b[unchecked], "y");
+        let (pairs, vacant) = a.into_raw_parts();
+        let rebuilt = SlotMap::from_raw_parts(pairs, vacant).unwrap();
+        assert_eq!(rebuilt[x], "x");
+        assert_eq!(rebuilt.get(y), None);
+    }
+
+    #[test]
+    fn test_key_vec() {
+        let mut slotmap = SlotMap::new();
+        assert!(slotmap.key_vec().is_empty());
+        let keys = slotmap.insert_many(0..10);
+        slotmap.remove(keys[3]);
+        slotmap.remove(keys[7]);
+        let key_vec = slotmap.key_vec();
+        assert_eq!(key_vec.len(), slotmap.len());
+        assert_eq!(key_vec, slotmap.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_contains_all() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..5);
+        assert!(slotmap.contains_all(&keys));
+        assert!(slotmap.contains_all(&[keys[1], keys[1]]));
+        assert!(slotmap.contains_all(&[]));
+        slotmap.remove(keys[2]);
+        assert!(!slotmap.contains_all(&keys));
+        assert!(slotmap.contains_all(&[keys[0], keys[4]]));
+        assert!(!slotmap.contains_all(&[Key::null()]));
+    }
+
+    #[test]
+    fn test_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(slotmap: &SlotMap<i32>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            slotmap.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut a = SlotMap::new();
+        let keys = a.insert_many(0..3);
+        a.remove(keys[0]);
+        let _ = a.insert(0);
+
+        let mut b = SlotMap::new();
+        let key = b.insert(0);
+        b.remove(key);
+        for x in 0..3 {
+            let _ = b.insert(x);
+        }
+
+        assert!(a == b);
+        assert!(!a.values().eq(b.values()));
+        assert_eq!(hash(&a), hash(&b));
+        *b.values_mut().next().unwrap() = 10;
+        assert_ne!(hash(&a), hash(&b));
+        assert_ne!(hash(&SlotMap::new()), hash(&a));
     }
-}
 
-impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0
-            .by_ref()
-            .map(|item| (item.key, &mut item.value))
-            .next_back()
+    #[test]
+    fn test_forget_free_slots() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..5);
+        slotmap.remove(keys[1]);
+        slotmap.remove(keys[4]);
+        slotmap.forget_free_slots();
+        let fresh = slotmap.insert_many(5..8);
+        assert_eq!(fresh.iter().map(Key::index).collect::<Vec<_>>(), [5, 6, 7]);
+        assert_eq!(slotmap.get(keys[1]), None);
+        assert_eq!(slotmap[keys[2]], 2);
+        slotmap.remove(fresh[0]);
+        assert_eq!(slotmap.insert(8).index(), 5);
+        slotmap.remove(fresh[2]);
+        slotmap.remove(fresh[1]);
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.slots.len(), 6);
+        assert_eq!(slotmap.insert(9).index(), 6);
     }
-}
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0
-            .by_ref()
-            .map(|item| (item.key, item.value))
-            .next_back()
+    #[test]
+    fn test_into_iter_peek() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..5);
+        slotmap.remove(keys[1]);
+        let mut iter = slotmap.clone().into_iter();
+        let mut taken = Vec::new();
+        while let Some((_, &value)) = iter.peek() {
+            if value == 2 {
+                break;
+            }
+            let peeked = iter.peek().map(|(key, &value)| (key, value));
+            let next = iter.next();
+            assert_eq!(peeked, next);
+            taken.push(next.unwrap().1);
+        }
+        assert_eq!(taken, [0, 4]);
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next_back(), Some((keys[3], 3)));
+        assert_eq!(iter.peek(), Some((keys[2], &2)));
+        assert_eq!(iter.next(), Some((keys[2], 2)));
+        assert_eq!(iter.peek(), None);
     }
-}
 
-impl<'a, T> DoubleEndedIterator for Values<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next_back()
+    #[test]
+    fn test_count_matching() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..10);
+        assert_eq!(slotmap.count_matching(|value| value % 2 == 0), 5);
+        slotmap.remove(keys[4]);
+        slotmap.remove(keys[5]);
+        assert_eq!(slotmap.count_matching(|value| value % 2 == 0), 4);
+        assert_eq!(slotmap.count_matching(|_| true), slotmap.len());
+        assert_eq!(SlotMap::<i32>::new().count_matching(|_| true), 0);
     }
-}
 
-impl<'a, T> DoubleEndedIterator for ValuesMut<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next_back()
+    #[test]
+    fn test_remove_reporting() {
+        let mut slotmap = SlotMap::new();
+        let keys = slotmap.insert_many(0..5);
+        let position = |slotmap: &SlotMap<i32>, key| slotmap.keys().position(|k| k == key);
+        assert_eq!(position(&slotmap, keys[4]), Some(4));
+        assert_eq!(slotmap.remove_reporting(keys[1]), Some((1, Some(keys[4]))));
+        assert_eq!(position(&slotmap, keys[4]), Some(1));
+        assert_eq!(slotmap.remove_reporting(keys[1]), None);
+        assert_eq!(slotmap.remove_reporting(keys[3]), Some((3, None)));
+        assert_eq!(slotmap.remove_reporting(keys[0]), Some((0, Some(keys[2]))));
+        assert_eq!(slotmap.keys().collect::<Vec<_>>(), [keys[2], keys[4]]);
+        assert_eq!(slotmap.insert(5).index(), keys[0].index());
     }
-}
 
-impl<T> DoubleEndedIterator for IntoValues<T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(_, value)| value).next_back()
+    #[test]
+    fn test_generation() {
+        for value in [0, 1, 1000, u64::from(u32::MAX)] {
+            let generation = Generation::new(value);
+            assert_eq!(generation.value(), value);
+            assert_eq!(Generation::new(generation.value()), generation);
+        }
+        assert!(Generation::new(1) > Generation::new(0));
+        assert_eq!(Generation::new(1).next(), Some(Generation::new(2)));
+        assert_eq!(Generation(RawGeneration::MAX).next(), None);
+        let mut slotmap = SlotMap::new();
+        let key = slotmap.insert(());
+        slotmap.remove(key);
+        let key = slotmap.insert(());
+        assert_eq!(Generation::new(key.generation()), Generation::new(1));
     }
-}
 
-impl<'a, T> DoubleEndedIterator for Keys<'a, T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.0.by_ref().map(|(key, _)| key).next_back()
+    #[test]
+    fn test_remove_only_and_non_last() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        assert_eq!(slotmap.remove(a), Some("a"));
+        assert!(slotmap.is_empty());
+        assert_eq!(slotmap.remove(a), None);
+
+        let keys = slotmap.insert_many(["b", "c", "d"]);
+        assert_eq!(slotmap.remove_no_recycle(keys[0]), Some("b"));
+        assert_eq!(slotmap.get(keys[0]), None);
+        assert_eq!(slotmap[keys[1]], "c");
+        assert_eq!(slotmap[keys[2]], "d");
+        assert_eq!(slotmap.remove(keys[2]), Some("d"));
+        assert_eq!(slotmap.remove(keys[1]), Some("c"));
+        assert!(slotmap.is_empty());
+        assert_eq!(slotmap.remove(keys[1]), None);
     }
-}
 
-impl<'a, T> IntoIterator for &'a SlotMap<T> {
-    type Item = (Key, &'a T);
-    type IntoIter = Iter<'a, T>;
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    #[test]
+    fn test_get_mut_or_default() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        *slotmap.get_mut_or_default(a).unwrap() += 10;
+        assert_eq!(slotmap[a], 11);
+        slotmap.remove(b);
+        let next = Key {
+            generation: b.generation.next().unwrap(),
+            ..b
+        };
+        *slotmap.get_mut_or_default(next).unwrap() += 5;
+        assert_eq!(slotmap[next], 5);
+        assert_eq!(slotmap.get(b), None);
+        assert_eq!(slotmap.len(), 2);
     }
-}
 
-impl<'a, T> IntoIterator for &'a mut SlotMap<T> {
-    type Item = (Key, &'a mut T);
-    type IntoIter = IterMut<'a, T>;
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter_mut()
+    #[test]
+    fn test_get_mut_or_default_stale() {
+        let mut slotmap = SlotMap::<i32>::new();
+        let a = slotmap.insert(1);
+        slotmap.remove(a);
+        assert_eq!(slotmap.get_mut_or_default(a), None);
+        assert!(slotmap.is_empty());
     }
-}
 
-impl<T> IntoIterator for SlotMap<T> {
-    type Item = (Key, T);
-    type IntoIter = IntoIter<T>;
-    fn into_iter(self) -> Self::IntoIter {
-        IntoIter(self.items.into_iter())
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_get_mut_or_default_out_of_range() {
+        let mut slotmap = SlotMap::<i32>::new();
+        let mut other = SlotMap::new();
+        let key = other.insert(1);
+        slotmap.get_mut_or_default(key);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
     #[test]
-    fn test_insert_get_remove() {
+    #[cfg(feature = "insertion_order")]
+    fn test_iter_by_insertion() {
         let mut slotmap = SlotMap::new();
-        let a = slotmap.insert("a");
-        let b = slotmap.insert("b");
-        let c = slotmap.insert("c");
-        assert_eq!(*slotmap.get(a).unwrap(), "a");
-        assert_eq!(*slotmap.get(b).unwrap(), "b");
-        assert_eq!(*slotmap.get(c).unwrap(), "c");
-        assert_eq!(*slotmap.get_mut(a).unwrap(), "a");
-        assert_eq!(*slotmap.get_mut(b).unwrap(), "b");
-        assert_eq!(*slotmap.get_mut(c).unwrap(), "c");
+        let keys = slotmap.insert_many(0..5);
+        slotmap.remove(keys[2]);
+        let a = slotmap.insert(5);
+        assert_eq!(a.index(), keys[2].index());
+        let order = [
+            (keys[0], &0),
+            (keys[1], &1),
+            (keys[3], &3),
+            (keys[4], &4),
+            (a, &5),
+        ];
+        assert_ne!(slotmap.iter().collect::<Vec<_>>(), order);
+        assert_eq!(slotmap.iter_by_insertion().collect::<Vec<_>>(), order);
+        let clone = slotmap.clone();
+        assert!(clone.iter_by_insertion().eq(slotmap.iter_by_insertion()));
+        let (pairs, vacant) = slotmap.into_raw_parts();
+        let mut rebuilt = SlotMap::from_raw_parts(pairs, vacant).unwrap();
+        assert_eq!(rebuilt.iter_by_insertion().collect::<Vec<_>>(), order);
+        let b = rebuilt.insert(6);
+        assert_eq!(rebuilt.iter_by_insertion().last(), Some((b, &6)));
     }
 
     #[test]
-    fn test_remove() {
+    fn test_remove_and_next_key() {
         let mut slotmap = SlotMap::new();
         let a = slotmap.insert("a");
         let b = slotmap.insert("b");
-        let c = slotmap.insert("c");
-        assert_eq!(slotmap.remove(a).unwrap(), "a");
-        assert_eq!(slotmap.remove(b).unwrap(), "b");
-        assert_eq!(slotmap.remove(c).unwrap(), "c");
-        assert_eq!(slotmap.get(a), None);
-        assert_eq!(slotmap.get(b), None);
-        assert_eq!(slotmap.get(c), None);
+        let (value, next) = slotmap.remove_and_next_key(a).unwrap();
+        assert_eq!(value, "a");
+        assert_eq!(next.index(), a.index());
+        assert_eq!(next.generation(), a.generation() + 1);
+        assert_eq!(slotmap.get(next), None);
+        assert_eq!(slotmap.remove_and_next_key(a), None);
+        assert_eq!(slotmap.insert("c"), next);
+        assert_eq!(slotmap[next], "c");
+        assert_eq!(slotmap[b], "b");
+
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        slotmap.items[0].key.generation = Generation(RawGeneration::MAX);
+        let a = Key {
+            generation: Generation(RawGeneration::MAX),
+            ..a
+        };
+        assert_eq!(slotmap.remove_and_next_key(a), Some(("a", Key::null())));
+        assert_ne!(slotmap.insert("b").index(), a.index());
     }
 
     #[test]
-    fn test_len() {
+    fn test_cursor_mut() {
         let mut slotmap = SlotMap::new();
-        assert!(slotmap.is_empty());
-        let a = slotmap.insert(());
-        let b = slotmap.insert(());
-        let c = slotmap.insert(());
-        assert_eq!(slotmap.len(), 3);
-        slotmap.remove(a);
-        slotmap.remove(b);
-        slotmap.remove(c);
-        assert!(slotmap.is_empty());
+        let keys = slotmap.insert_many(0..10);
+        let mut cursor = slotmap.cursor_mut();
+        let mut visited = Vec::new();
+        let mut removed = Vec::new();
+        while let Some((key, value)) = cursor.current() {
+            visited.push(key);
+            *value *= 10;
+            if visited.len() % 2 == 1 {
+                assert_eq!(cursor.remove_current(), Some(key.index() * 10));
+                removed.push(key);
+            } else {
+                cursor.advance();
+            }
+        }
+        cursor.advance();
+        assert!(cursor.current().is_none());
+        assert_eq!(cursor.remove_current(), None);
+        visited.sort_unstable();
+        assert_eq!(visited, keys);
+        assert_eq!(removed.len(), 5);
+        for key in keys {
+            assert_eq!(
+                slotmap.get(key).copied(),
+                (!removed.contains(&key)).then_some(key.index() * 10)
+            );
+        }
     }
 
     #[test]
-    fn test_uaf() {
+    fn test_into_iter_non_clone() {
+        #[derive(Debug, PartialEq)]
+        struct NonClone(i32);
+
         let mut slotmap = SlotMap::new();
-        let a = slotmap.insert("a");
-        slotmap.remove(a);
-        let b = slotmap.insert("b");
-        assert_eq!(a.index, b.index);
-        assert_eq!(slotmap.get(a), None);
-        assert_eq!(*slotmap.get(b).unwrap(), "b");
+        let keys = slotmap.insert_many((0..5).map(NonClone));
+        slotmap.remove(keys[1]);
+        let values = slotmap
+            .into_iter()
+            .rev()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [NonClone(3), NonClone(2), NonClone(4), NonClone(0)]);
     }
 
     #[test]
-    fn test_iter() {
+    fn test_retain_with_removed() {
         let mut slotmap = SlotMap::new();
-        let mut keys = Vec::new();
-        for x in 0..10 {
-            keys.push(slotmap.insert(x));
+        let keys = slotmap.insert_many(0..20);
+        let mut visited = Vec::new();
+        let removed = slotmap.retain_with_removed(|key, &value| {
+            visited.push(key);
+            value % 3 != 0
+        });
+        visited.sort_unstable();
+        assert_eq!(visited, keys);
+        assert_eq!(removed.len(), 7);
+        for &(key, value) in &removed {
+            assert_eq!(value % 3, 0);
+            assert_eq!(keys[value], key);
+            assert!(!slotmap.contains_key(key));
         }
-        let mut it = slotmap.iter();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(
-            a.as_slice(),
-            [(_, 0), (_, 1), (_, 2), (_, 3), (_, 4)]
-        ));
-        assert!(matches!(
-            dbg!(b.as_slice()),
-            [(_, 9), (_, 8), (_, 7), (_, 6), (_, 5)]
-        ));
+        let mut all = removed;
+        all.extend(slotmap.iter().map(|(key, &value)| (key, value)));
+        all.sort_unstable();
+        assert_eq!(all, keys.into_iter().zip(0..20).collect::<Vec<_>>());
     }
 
     #[test]
-    fn test_iter_mut() {
+    fn test_shrink_free_list() {
         let mut slotmap = SlotMap::new();
-        let mut keys = Vec::new();
-        for x in 0..10 {
-            keys.push(slotmap.insert(x));
+        let keys = slotmap.insert_many(0..100);
+        for key in keys {
+            slotmap.remove(key);
         }
-        for (_, val) in &mut slotmap {
-            *val *= 2;
+        let _ = slotmap.insert_many(0..100);
+        assert!(slotmap.free.is_empty());
+        let capacity = slotmap.free.capacity();
+        assert!(capacity >= 100);
+        slotmap.shrink_free_list();
+        assert!(slotmap.free.capacity() < capacity);
+
+        let keys = slotmap.insert_many(0..100);
+        for key in keys {
+            slotmap.remove(key);
         }
-        let mut it = slotmap.iter();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(
-            a.as_slice(),
-            [(_, 0), (_, 2), (_, 4), (_, 6), (_, 8)]
-        ));
-        assert!(matches!(
-            dbg!(b.as_slice()),
-            [(_, 18), (_, 16), (_, 14), (_, 12), (_, 10)]
-        ));
+        let _ = slotmap.insert_many(0..100);
+        slotmap.shrink_to_fit();
+        assert_eq!(slotmap.free.capacity(), 0);
+        assert_eq!(slotmap.len(), 200);
     }
 
     #[test]
-    fn test_into_iter() {
+    fn test_get_all() {
         let mut slotmap = SlotMap::new();
-        let mut keys = Vec::new();
-        for x in 0..10 {
-            keys.push(slotmap.insert(x));
-        }
-        let mut it = slotmap.into_iter();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(
-            a.as_slice(),
-            [(_, 0), (_, 1), (_, 2), (_, 3), (_, 4)]
-        ));
-        assert!(matches!(
-            dbg!(b.as_slice()),
-            [(_, 9), (_, 8), (_, 7), (_, 6), (_, 5)]
-        ));
+        let keys = slotmap.insert_many(0..4);
+        slotmap.remove(keys[1]);
+        slotmap.remove(keys[3]);
+        let _ = slotmap.insert(4);
+        assert_eq!(
+            slotmap.get_all(&[keys[3], keys[0], keys[1], keys[2], keys[0]]),
+            [None, Some(&0), None, Some(&2), Some(&0)]
+        );
+        assert!(slotmap.get_all(&[]).is_empty());
+        assert_eq!(slotmap.get_all(&[Key::null()]), [None]);
     }
 
     #[test]
-    fn test_values() {
+    fn test_insert_mut() {
         let mut slotmap = SlotMap::new();
-        for x in 0..10 {
-            let _ = slotmap.insert(x);
-        }
-        let mut it = slotmap.values();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
-        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+        let keys = slotmap.insert_many(0..3);
+        slotmap.remove(keys[0]);
+        let (key, value) = slotmap.insert_mut(10);
+        *value += 1;
+        assert_eq!(key.index(), keys[0].index());
+        assert_eq!(slotmap.get(key), Some(&11));
+        assert_eq!(slotmap[keys[1]], 1);
+        assert_eq!(slotmap[keys[2]], 2);
     }
 
     #[test]
-    fn test_values_mut() {
+    fn test_key_debug() {
         let mut slotmap = SlotMap::new();
-        for x in 0..10 {
-            let _ = slotmap.insert(x);
-        }
-        let mut it = slotmap.values_mut();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
-        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+        let keys = slotmap.insert_many(0..6);
+        assert_eq!(format!("{:?}", keys[5]), "Key(5@0)");
+        slotmap.remove(keys[5]);
+        slotmap.remove(keys[5]);
+        let key = slotmap.insert(0);
+        slotmap.remove(key);
+        let key = slotmap.insert(0);
+        assert_eq!(format!("{key:?}"), "Key(5@2)");
+        assert_eq!(format!("{:?}", Key::null()), "Key(null)");
+        assert_eq!(format!("{:?}", Key::from_ffi(3 | (7 << 32))), "Key(3@7)");
     }
 
+    #[cfg(feature = "key32")]
     #[test]
-    fn test_into_values() {
+    fn test_try_insert_retired_slot() {
         let mut slotmap = SlotMap::new();
-        for x in 0..10 {
-            let _ = slotmap.insert(x);
-        }
-        let mut it = slotmap.into_values();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().collect::<Vec<_>>();
-        assert!(matches!(a.as_slice(), [0, 1, 2, 3, 4]));
-        assert!(matches!(b.as_slice(), [9, 8, 7, 6, 5]));
+        let key = slotmap.try_insert(0).unwrap();
+        slotmap.items[0].key.generation = Generation(RawGeneration::MAX);
+        let key = Key {
+            generation: Generation(RawGeneration::MAX),
+            ..key
+        };
+        assert_eq!(slotmap.remove(key), Some(0));
+        assert!(slotmap.free.is_empty());
+        let next = slotmap.try_insert(1).unwrap();
+        assert_ne!(next.index(), key.index());
+        assert_eq!(slotmap.get(key), None);
+
+        // Every slot is either live or retired, so once the index space is
+        // full there is nothing left to insert into.
+        let exhausted = RawIndex::MAX as usize;
+        let error = slotmap.insert_error(exhausted).map(|error| error(2));
+        assert_eq!(error, Some(InsertError::Exhausted(2)));
+        assert_eq!(
+            slotmap.insert_error(exhausted - 1).map(|error| error(2)),
+            None
+        );
+        slotmap.remove(next);
+        assert_eq!(slotmap.insert_error(exhausted).map(|error| error(2)), None);
+        slotmap.set_max_capacity(0);
+        let error = slotmap.insert_error(exhausted).map(|error| error(2));
+        assert_eq!(error, Some(InsertError::Full(2)));
     }
 
     #[test]
-    fn test_keys() {
+    fn test_drain_filter() {
         let mut slotmap = SlotMap::new();
-        let mut keys = Vec::new();
-        for x in 0..10 {
-            keys.push(slotmap.insert(x));
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        let mut drained = slotmap
+            .drain_filter(|_, val| *val % 3 == 0)
+            .collect::<Vec<_>>();
+        drained.sort_unstable();
+        assert_eq!(
+            drained,
+            [(keys[0], 0), (keys[3], 3), (keys[6], 6), (keys[9], 9)]
+        );
+        assert_eq!(slotmap.len(), 6);
+        for (x, &key) in keys.iter().enumerate() {
+            assert_eq!(slotmap.get(key), (x % 3 != 0).then_some(&x));
         }
-        let mut it = slotmap.keys();
-        let a = it.by_ref().take(5).collect::<Vec<_>>();
-        let b = it.by_ref().rev().take(5).collect::<Vec<_>>();
-        assert_eq!(keys[0], a[0]);
-        assert_eq!(keys[1], a[1]);
-        assert_eq!(keys[2], a[2]);
-        assert_eq!(keys[3], a[3]);
-        assert_eq!(keys[4], a[4]);
-        assert_eq!(keys[9], b[0]);
-        assert_eq!(keys[8], b[1]);
-        assert_eq!(keys[7], b[2]);
-        assert_eq!(keys[6], b[3]);
-        assert_eq!(keys[5], b[4]);
     }
 
     #[test]
-    fn test_retain() {
-        let mut slotmap = SlotMap::new();
+    fn test_drain_filter_early_drop() {
+        let mut slotmap = (0..10).collect::<SlotMap<_>>();
+        let mut visited = 0;
+        let mut iter = slotmap.drain_filter(|_, val| {
+            visited += 1;
+            *val % 2 == 0
+        });
+        assert!(iter.next().is_some());
+        drop(iter);
+        assert_eq!(visited, 10);
+        assert_eq!(slotmap.len(), 5);
+        assert!(slotmap.values().all(|val| val % 2 == 1));
+    }
 
-        for x in 0..10 {
-            let _ = slotmap.insert(x);
+    #[test]
+    fn test_drain_filter_swap_remove() {
+        // Removing the first value swaps the last one into its place, which
+        // must still be given to the predicate.
+        let mut slotmap = (0..5).collect::<SlotMap<_>>();
+        let mut seen = Vec::new();
+        let drained = slotmap
+            .drain_filter(|_, val| {
+                seen.push(*val);
+                *val == 0 || *val == 4
+            })
+            .map(|(_, val)| val)
+            .collect::<Vec<_>>();
+        assert_eq!(drained, [0, 4]);
+        assert_eq!(seen, [0, 4, 3, 1, 2]);
+        let mut rest = slotmap.values().copied().collect::<Vec<_>>();
+        rest.sort_unstable();
+        assert_eq!(rest, [1, 2, 3]);
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    // Calling `check` only compiles if the type isn't `Send`, because
+    // otherwise both impls apply and the call is ambiguous.
+    trait AmbiguousIfSend<A> {
+        fn check() {}
+    }
+    impl<T: ?Sized> AmbiguousIfSend<()> for T {}
+    impl<T: ?Sized + Send> AmbiguousIfSend<u8> for T {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<SlotMap<i32>>();
+        assert_send_sync::<Key>();
+        assert_send_sync::<Iter<'_, i32>>();
+        assert_send_sync::<Drain<'_, i32>>();
+        <SlotMap<std::rc::Rc<i32>> as AmbiguousIfSend<_>>::check();
+    }
+
+    #[test]
+    fn test_default_matches_new() {
+        let mut a = SlotMap::default();
+        let mut b = SlotMap::new();
+        assert_eq!(a.capacity(), 0);
+        assert_eq!(a.memory_usage(), b.memory_usage());
+        assert_eq!(a, b);
+        let x = a.insert(1);
+        let y = b.insert(1);
+        assert_eq!((x.index(), x.generation()), (y.index(), y.generation()));
+        assert_eq!(a.try_insert(2).is_ok(), b.try_insert(2).is_ok());
+    }
+
+    #[test]
+    fn test_iter_with() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let keys = (0..6).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        for &key in keys.iter().step_by(2) {
+            secondary.insert(key, slotmap[key] * 10);
+        }
+        slotmap.remove(keys[4]);
+        let zipped = slotmap.iter_with(&secondary).collect::<Vec<_>>();
+        assert_eq!(zipped.len(), 5);
+        for (key, value, extra) in zipped {
+            if value % 2 == 0 {
+                assert_eq!(extra, Some(&(value * 10)));
+            } else {
+                assert_eq!(extra, None);
+            }
+            assert_eq!(extra, secondary.get(key));
         }
+    }
 
-        slotmap.retain(|(_, _)| true);
-        assert_eq!(slotmap.len(), 10);
+    #[test]
+    fn test_insert_low() {
+        let mut slotmap = SlotMap::new();
+        let keys = (0..8).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        for &i in &[5, 1, 6, 3] {
+            slotmap.remove(keys[i]);
+        }
+        let indices = (0..5)
+            .map(|x| slotmap.insert_low(x).index())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, [1, 3, 5, 6, 8]);
+        assert_eq!(slotmap.len(), 9);
+    }
 
-        slotmap.retain(|(_, val)| *val >= 5);
-        assert_eq!(slotmap.len(), 5);
+    #[test]
+    fn test_debug_slot_map() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert("a");
+        let _ = slotmap.insert("b");
+        let _ = slotmap.insert("c");
+        slotmap.remove(a);
+        assert_eq!(
+            slotmap.debug_slot_map(),
+            [(0, None), (1, Some(1)), (2, Some(0))]
+        );
+        let d = slotmap.insert("d");
+        assert_eq!(d.index(), 0);
+        assert_eq!(
+            slotmap.debug_slot_map(),
+            [(0, Some(2)), (1, Some(1)), (2, Some(0))]
+        );
+        for (index, item_index) in slotmap.debug_slot_map() {
+            assert_eq!(slotmap.items[item_index.unwrap()].key.index(), index);
+        }
+    }
 
-        slotmap.retain(|(_, _)| false);
-        assert!(slotmap.is_empty());
+    #[test]
+    fn test_insert_with_key_panic() {
+        let mut slotmap = SlotMap::new();
+        let a = slotmap.insert(0);
+        let _ = slotmap.insert(1);
+        slotmap.remove(a);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = slotmap.insert_with_key(|_| panic!("closure panicked"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(slotmap.len(), 1);
+        let b = slotmap.insert(2);
+        assert_eq!(b.index(), a.index());
+        assert_eq!(slotmap.slots.len(), 2);
     }
 }
@@ -0,0 +1,434 @@
+//! A map for associating extra data with the keys of a [`SlotMap`](crate::SlotMap).
+//! ```
+//! use slotmap::{SecondaryMap, SlotMap};
+//!
+//! let mut names = SlotMap::new();
+//! let mut ages = SecondaryMap::new();
+//!
+//! let a = names.insert("alice");
+//! let b = names.insert("bob");
+//! ages.insert(a, 30);
+//!
+//! assert_eq!(ages.get(a), Some(&30));
+//! assert_eq!(ages.get(b), None);
+//! ```
+
+use crate::{invalid_key, Generation, Key, MapId, SlotMap};
+use std::fmt::{self, Debug};
+use std::iter::FusedIterator;
+use std::ops::{Index, IndexMut};
+
+type Entry<V> = Option<(Generation, MapId, V)>;
+
+pub struct Iter<'a, V>(std::iter::Enumerate<std::slice::Iter<'a, Entry<V>>>);
+pub struct IterMut<'a, V>(std::iter::Enumerate<std::slice::IterMut<'a, Entry<V>>>);
+
+/// A map from the keys of a slotmap to values of another type.
+///
+/// # Performance
+/// Values are stored in a vector indexed by the key's slot index, so access is
+/// constant time without any indirection. Iteration visits every slot up to
+/// the highest inserted index, so it is slower than iterating a slotmap when
+/// only a few keys have associated values.
+#[derive(Clone, Default)]
+pub struct SecondaryMap<V> {
+    slots: Vec<Entry<V>>,
+    len: usize,
+}
+
+impl<V> SecondaryMap<V> {
+    #[must_use]
+    pub fn new() -> SecondaryMap<V> {
+        SecondaryMap {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Associates a value with a key, returning the value previously
+    /// associated with the same key.
+    ///
+    /// Values associated with older generations of the key's slot are
+    /// replaced. If the key is older than a key that was already inserted for
+    /// the same slot it is stale, so nothing is inserted and `None` is
+    /// returned.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = SecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// assert_eq!(secondary.insert(key, 1), None);
+    /// assert_eq!(secondary.insert(key, 2), Some(1));
+    /// ```
+    pub fn insert(&mut self, key: Key, value: V) -> Option<V> {
+        if key.is_null() {
+            return None;
+        }
+        let index = key.index();
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        match &mut self.slots[index] {
+            Some((generation, map_id, old))
+                if *generation == key.generation && map_id.accepts(key.map_id) =>
+            {
+                Some(std::mem::replace(old, value))
+            }
+            Some((generation, _, _)) if *generation > key.generation => None,
+            slot => {
+                if slot.is_none() {
+                    self.len += 1;
+                }
+                *slot = Some((key.generation, key.map_id, value));
+                None
+            }
+        }
+    }
+
+    /// Removes the value associated with a key.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = SecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// assert_eq!(secondary.remove(key), Some(1));
+    /// assert_eq!(secondary.remove(key), None);
+    /// ```
+    pub fn remove(&mut self, key: Key) -> Option<V> {
+        let slot = self.slots.get_mut(key.index())?;
+        match slot {
+            Some((generation, map_id, _))
+                if *generation == key.generation && map_id.accepts(key.map_id) =>
+            {
+                self.len -= 1;
+                slot.take().map(|(_, _, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a shared reference to the value associated with the key.
+    /// This will return `None` if provided with a stale key.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = SecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// assert_eq!(secondary.get(key), Some(&1));
+    /// ```
+    #[must_use]
+    pub fn get(&self, key: Key) -> Option<&V> {
+        match self.slots.get(key.index()) {
+            Some(Some((generation, map_id, value)))
+                if *generation == key.generation && map_id.accepts(key.map_id) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns an exclusive reference to the value associated with the key and
+    /// otherwise behaves indentically to `get`.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = SecondaryMap::new();
+    /// let key = slotmap.insert("an example value");
+    /// secondary.insert(key, 1);
+    /// *secondary.get_mut(key).unwrap() += 1;
+    /// assert_eq!(secondary.get(key), Some(&2));
+    /// ```
+    #[must_use]
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut V> {
+        match self.slots.get_mut(key.index()) {
+            Some(Some((generation, map_id, value)))
+                if *generation == key.generation && map_id.accepts(key.map_id) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks whether a value is associated with the key.
+    #[must_use]
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of keys that have an associated value.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no keys have an associated value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator that yields a (key, value) tuple for every key that
+    /// has an associated value, in order of the key's slot index.
+    /// ##### Example
+    /// ```
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut slotmap = SlotMap::new();
+    /// let mut secondary = SecondaryMap::new();
+    ///
+    /// for i in 0..10 {
+    ///     let key = slotmap.insert(i);
+    ///     secondary.insert(key, i * 2);
+    /// }
+    ///
+    /// for (key, val) in &secondary {
+    ///     println!("{:?}: {}", key, val);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter(self.slots.iter().enumerate())
+    }
+
+    /// See [`SecondaryMap::iter`](crate::SecondaryMap::iter)
+    #[must_use]
+    pub fn iter_mut(&mut self) -> IterMut<'_, V> {
+        IterMut(self.slots.iter_mut().enumerate())
+    }
+}
+
+/// Returns exclusive references to the values associated with a key in both
+/// a slotmap and one of its secondary maps, or `None` if either of them has no
+/// value for the key.
+/// ##### Example
+/// ```
+/// use slotmap::{get_both_mut, SecondaryMap, SlotMap};
+///
+/// let mut positions = SlotMap::new();
+/// let mut velocities = SecondaryMap::new();
+/// let key = positions.insert(0.0);
+/// velocities.insert(key, 1.0);
+///
+/// if let Some((position, velocity)) = get_both_mut(&mut positions, &mut velocities, key) {
+///     *position += *velocity;
+///     *velocity /= 2.0;
+/// }
+///
+/// assert_eq!(positions[key], 1.0);
+/// assert_eq!(velocities[key], 0.5);
+/// ```
+pub fn get_both_mut<'a, T, V>(
+    primary: &'a mut SlotMap<T>,
+    secondary: &'a mut SecondaryMap<V>,
+    key: Key,
+) -> Option<(&'a mut T, &'a mut V)> {
+    Some((primary.get_mut(key)?, secondary.get_mut(key)?))
+}
+
+impl<V> Index<Key> for SecondaryMap<V> {
+    type Output = V;
+    fn index(&self, index: Key) -> &Self::Output {
+        match self.get(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+impl<V> IndexMut<Key> for SecondaryMap<V> {
+    fn index_mut(&mut self, index: Key) -> &mut Self::Output {
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => invalid_key(index),
+        }
+    }
+}
+
+impl<V: Debug> Debug for SecondaryMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (Key, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|(index, slot)| {
+            let (generation, map_id, value) = slot.as_ref()?;
+            Some((Key::new(index, *generation, *map_id)?, value))
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
+impl<'a, V> Iterator for IterMut<'a, V> {
+    type Item = (Key, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|(index, slot)| {
+            let (generation, map_id, value) = slot.as_mut()?;
+            Some((Key::new(index, *generation, *map_id)?, value))
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
+impl<V> DoubleEndedIterator for Iter<'_, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().rev().find_map(|(index, slot)| {
+            let (generation, map_id, value) = slot.as_ref()?;
+            Some((Key::new(index, *generation, *map_id)?, value))
+        })
+    }
+}
+
+impl<V> DoubleEndedIterator for IterMut<'_, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().rev().find_map(|(index, slot)| {
+            let (generation, map_id, value) = slot.as_mut()?;
+            Some((Key::new(index, *generation, *map_id)?, value))
+        })
+    }
+}
+
+impl<V> FusedIterator for Iter<'_, V> {}
+impl<V> FusedIterator for IterMut<'_, V> {}
+
+impl<'a, V> IntoIterator for &'a SecondaryMap<V> {
+    type Item = (Key, &'a V);
+    type IntoIter = Iter<'a, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a mut SecondaryMap<V> {
+    type Item = (Key, &'a mut V);
+    type IntoIter = IterMut<'a, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let a = slotmap.insert("a");
+        let b = slotmap.insert("b");
+        let c = slotmap.insert("c");
+        assert_eq!(secondary.insert(a, 1), None);
+        assert_eq!(secondary.insert(c, 3), None);
+        assert_eq!(secondary.insert(c, 4), Some(3));
+        assert_eq!(secondary.len(), 2);
+        assert_eq!(secondary.get(a), Some(&1));
+        assert_eq!(secondary.get(b), None);
+        assert_eq!(secondary.get(c), Some(&4));
+        *secondary.get_mut(a).unwrap() += 1;
+        assert_eq!(secondary[a], 2);
+        assert_eq!(secondary.remove(a), Some(2));
+        assert_eq!(secondary.remove(a), None);
+        assert_eq!(secondary.len(), 1);
+    }
+
+    #[test]
+    fn test_stale_keys() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let a = slotmap.insert("a");
+        secondary.insert(a, 1);
+        slotmap.remove(a);
+        let b = slotmap.insert("b");
+        assert_eq!(a.index(), b.index());
+        assert_eq!(secondary.get(b), None);
+        assert_eq!(secondary.insert(b, 2), None);
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary.get(a), None);
+        assert_eq!(secondary.insert(a, 3), None);
+        assert_eq!(secondary.remove(a), None);
+        assert_eq!(secondary.get(b), Some(&2));
+        assert_eq!(secondary.insert(Key::null(), 4), None);
+        assert_eq!(secondary.get(Key::null()), None);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let keys = (0..10).map(|x| slotmap.insert(x)).collect::<Vec<_>>();
+        for (x, &key) in keys.iter().enumerate().filter(|(x, _)| x % 2 == 0) {
+            secondary.insert(key, x);
+        }
+        for (_, value) in &mut secondary {
+            *value *= 2;
+        }
+        let a = secondary.iter().collect::<Vec<_>>();
+        let b = secondary.iter().rev().collect::<Vec<_>>();
+        assert_eq!(
+            a,
+            [
+                (keys[0], &0),
+                (keys[2], &4),
+                (keys[4], &8),
+                (keys[6], &12),
+                (keys[8], &16)
+            ]
+        );
+        assert!(a.into_iter().eq(b.into_iter().rev()));
+    }
+
+    #[test]
+    fn test_default() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::default();
+        assert!(secondary.is_empty());
+        let key = slotmap.insert("a");
+        assert_eq!(secondary.insert(key, 1), None);
+        assert_eq!(secondary.get(key), Some(&1));
+    }
+
+    #[test]
+    fn test_get_both_mut() {
+        let mut slotmap = SlotMap::new();
+        let mut secondary = SecondaryMap::new();
+        let a = slotmap.insert(1);
+        let b = slotmap.insert(2);
+        secondary.insert(a, 10);
+        let (value, extra) = get_both_mut(&mut slotmap, &mut secondary, a).unwrap();
+        *value += 1;
+        *extra += *value;
+        assert_eq!((slotmap[a], secondary[a]), (2, 12));
+        assert!(get_both_mut(&mut slotmap, &mut secondary, b).is_none());
+        slotmap.remove(a);
+        assert!(get_both_mut(&mut slotmap, &mut secondary, a).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "no value for key with index 0 and generation 0")]
+    fn test_index_panic() {
+        let mut slotmap = SlotMap::new();
+        let secondary = SecondaryMap::<i32>::new();
+        let key = slotmap.insert("a");
+        let _ = secondary[key];
+    }
+}
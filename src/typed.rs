@@ -0,0 +1,256 @@
+//! A slotmap whose keys are tagged with a marker type, so keys for one map
+//! can't be used with another at compile time.
+//! ```
+//! use slotmap::TypedSlotMap;
+//!
+//! struct Players;
+//! struct Enemies;
+//!
+//! let mut players = TypedSlotMap::<Players, _>::new();
+//! let mut enemies = TypedSlotMap::<Enemies, _>::new();
+//!
+//! let alice = players.insert("alice");
+//! let _ = enemies.insert("goblin");
+//!
+//! assert_eq!(players[alice], "alice");
+//! ```
+//! Using a key with a map of a different marker type doesn't compile.
+//! ```compile_fail
+//! use slotmap::TypedSlotMap;
+//!
+//! struct Players;
+//! struct Enemies;
+//!
+//! let mut players = TypedSlotMap::<Players, _>::new();
+//! let enemies = TypedSlotMap::<Enemies, &str>::new();
+//!
+//! let alice = players.insert("alice");
+//! let _ = enemies.get(alice);
+//! ```
+
+use crate::{Key, SlotMap};
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// A [`Key`](crate::Key) that can only be used with a
+/// [`TypedSlotMap`](crate::TypedSlotMap) of the same marker type `M`.
+///
+/// The marker is never stored, so it doesn't need to implement any traits and
+/// the key is the same size as an untyped key.
+pub struct TypedKey<M> {
+    key: Key,
+    marker: PhantomData<fn() -> M>,
+}
+
+/// A [`SlotMap`](crate::SlotMap) that hands out
+/// [`TypedKey`](crate::TypedKey)s tagged with the marker type `M`.
+pub struct TypedSlotMap<M, T> {
+    slotmap: SlotMap<T>,
+    marker: PhantomData<fn() -> M>,
+}
+
+impl<M> TypedKey<M> {
+    fn new(key: Key) -> TypedKey<M> {
+        TypedKey {
+            key,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the untyped key.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+}
+
+impl<M> Clone for TypedKey<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for TypedKey<M> {}
+
+impl<M> PartialEq for TypedKey<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<M> Eq for TypedKey<M> {}
+
+impl<M> PartialOrd for TypedKey<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for TypedKey<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<M> Hash for TypedKey<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl<M> Debug for TypedKey<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.key.fmt(f)
+    }
+}
+
+impl<M> From<TypedKey<M>> for Key {
+    fn from(key: TypedKey<M>) -> Key {
+        key.key
+    }
+}
+
+impl<M, T> TypedSlotMap<M, T> {
+    /// See [`SlotMap::new`](crate::SlotMap::new)
+    #[must_use]
+    pub fn new() -> TypedSlotMap<M, T> {
+        TypedSlotMap {
+            slotmap: SlotMap::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
+    /// ##### Panics
+    /// See [`SlotMap::insert`](crate::SlotMap::insert)
+    #[must_use]
+    pub fn insert(&mut self, value: T) -> TypedKey<M> {
+        TypedKey::new(self.slotmap.insert(value))
+    }
+
+    /// See [`SlotMap::remove`](crate::SlotMap::remove)
+    pub fn remove(&mut self, key: TypedKey<M>) -> Option<T> {
+        self.slotmap.remove(key.key)
+    }
+
+    /// See [`SlotMap::get`](crate::SlotMap::get)
+    #[must_use]
+    pub fn get(&self, key: TypedKey<M>) -> Option<&T> {
+        self.slotmap.get(key.key)
+    }
+
+    /// See [`SlotMap::get_mut`](crate::SlotMap::get_mut)
+    #[must_use]
+    pub fn get_mut(&mut self, key: TypedKey<M>) -> Option<&mut T> {
+        self.slotmap.get_mut(key.key)
+    }
+
+    /// See [`SlotMap::contains_key`](crate::SlotMap::contains_key)
+    #[must_use]
+    pub fn contains_key(&self, key: TypedKey<M>) -> bool {
+        self.slotmap.contains_key(key.key)
+    }
+
+    /// See [`SlotMap::len`](crate::SlotMap::len)
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slotmap.len()
+    }
+
+    /// See [`SlotMap::is_empty`](crate::SlotMap::is_empty)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slotmap.is_empty()
+    }
+
+    /// See [`SlotMap::iter`](crate::SlotMap::iter)
+    pub fn iter(&self) -> impl Iterator<Item = (TypedKey<M>, &T)> {
+        self.slotmap
+            .iter()
+            .map(|(key, value)| (TypedKey::new(key), value))
+    }
+
+    /// See [`SlotMap::values`](crate::SlotMap::values)
+    #[must_use]
+    pub fn values(&self) -> crate::Values<'_, T> {
+        self.slotmap.values()
+    }
+
+    /// See [`SlotMap::values_mut`](crate::SlotMap::values_mut)
+    #[must_use]
+    pub fn values_mut(&mut self) -> crate::ValuesMut<'_, T> {
+        self.slotmap.values_mut()
+    }
+}
+
+impl<M, T> Default for TypedSlotMap<M, T> {
+    fn default() -> Self {
+        TypedSlotMap::new()
+    }
+}
+
+impl<M, T: Clone> Clone for TypedSlotMap<M, T> {
+    fn clone(&self) -> Self {
+        TypedSlotMap {
+            slotmap: self.slotmap.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M, T> Index<TypedKey<M>> for TypedSlotMap<M, T> {
+    type Output = T;
+    fn index(&self, index: TypedKey<M>) -> &Self::Output {
+        &self.slotmap[index.key]
+    }
+}
+
+impl<M, T> IndexMut<TypedKey<M>> for TypedSlotMap<M, T> {
+    fn index_mut(&mut self, index: TypedKey<M>) -> &mut Self::Output {
+        &mut self.slotmap[index.key]
+    }
+}
+
+impl<M, T: Debug> Debug for TypedSlotMap<M, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.slotmap.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Players;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut players = TypedSlotMap::<Players, _>::new();
+        let a = players.insert("alice");
+        let b = players.insert("bob");
+        assert_eq!(players.len(), 2);
+        assert_eq!(players.get(a), Some(&"alice"));
+        players[b] = "bobby";
+        assert_eq!(players[b], "bobby");
+        assert_eq!(players.remove(a), Some("alice"));
+        assert_eq!(players.get(a), None);
+        assert!(!players.contains_key(a));
+        assert_eq!(players.iter().collect::<Vec<_>>(), [(b, &"bobby")]);
+    }
+
+    #[test]
+    fn test_key() {
+        let mut players = TypedSlotMap::<Players, _>::new();
+        let a = players.insert(1);
+        let untyped: Key = a.into();
+        assert_eq!(untyped, a.key());
+        assert_eq!(
+            std::mem::size_of::<TypedKey<Players>>(),
+            std::mem::size_of::<Key>()
+        );
+        assert_eq!(format!("{a:?}"), format!("{untyped:?}"));
+    }
+}